@@ -0,0 +1,698 @@
+use std::collections::{BinaryHeap, VecDeque};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+
+use crate::prelude::Io;
+use crate::system;
+
+/// Async flavour of `Io`
+///
+/// `run` returns a plain `Future`, so an `AsyncIo` can be driven by any executor.
+/// `block_on` and `LocalExecutor` are provided for programs without one.
+pub trait AsyncIo: Sized {
+    type Output;
+
+    fn run(self) -> impl Future<Output = Self::Output>;
+
+    fn map<B, F>(self, f: F) -> AsyncIoMap<Self, F>
+    where
+        F: FnOnce(Self::Output) -> B,
+    {
+        AsyncIoMap { io: self, f }
+    }
+
+    fn pure<T>(t: T) -> AsyncIoPure<T> {
+        AsyncIoPure { io: t }
+    }
+
+    fn and_then<B, F>(self, f: F) -> AsyncIoBind<Self, F>
+    where
+        F: FnOnce(Self::Output) -> B + Clone,
+    {
+        AsyncIoBind { io: self, f }
+    }
+
+    fn then<Mb>(self, mb: Mb) -> AsyncIoBind<Self, impl FnOnce(Self::Output) -> Mb + Clone>
+    where
+        Mb: AsyncIo + Clone,
+    {
+        AsyncIoBind {
+            io: self,
+            f: |_| mb,
+        }
+    }
+
+    /// Blocking `Io` which drives this action on the current thread
+    fn to_io(self) -> BlockOnIo<Self> {
+        BlockOnIo { io: self }
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncIoMap<I, F> {
+    io: I,
+    f: F,
+}
+
+impl<A, F, B> AsyncIo for AsyncIoMap<A, F>
+where
+    A: AsyncIo,
+    F: FnOnce(A::Output) -> B,
+{
+    type Output = B;
+
+    async fn run(self) -> Self::Output {
+        (self.f)(self.io.run().await)
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncIoPure<A> {
+    io: A,
+}
+
+impl<A> AsyncIo for AsyncIoPure<A> {
+    type Output = A;
+
+    async fn run(self) -> Self::Output {
+        self.io
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncIoBind<A, F> {
+    io: A,
+    f: F,
+}
+
+impl<A, B, F> AsyncIo for AsyncIoBind<A, F>
+where
+    A: AsyncIo,
+    B: AsyncIo,
+    F: FnOnce(A::Output) -> B,
+{
+    type Output = B::Output;
+
+    async fn run(self) -> Self::Output {
+        (self.f)(self.io.run().await).run().await
+    }
+}
+
+// Adapters
+
+/// Runs a blocking `Io` on a dedicated thread and awaits its output.
+#[derive(Clone)]
+pub struct FromIo<I> {
+    io: I,
+}
+
+impl<I> AsyncIo for FromIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    type Output = I::Output;
+
+    async fn run(self) -> Self::Output {
+        let io = self.io;
+        Blocking::new(move || io.run()).await
+    }
+}
+
+pub fn from_io<I>(io: I) -> FromIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    FromIo { io }
+}
+
+#[derive(Clone)]
+pub struct BlockOnIo<A> {
+    io: A,
+}
+
+impl<A> Io for BlockOnIo<A>
+where
+    A: AsyncIo,
+{
+    type Output = A::Output;
+
+    fn run(self) -> Self::Output {
+        block_on(self.io.run())
+    }
+}
+
+// Executor
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drive a future to completion on the current thread.
+pub fn block_on<F>(fut: F) -> F::Output
+where
+    F: Future,
+{
+    let mut fut = std::pin::pin!(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+struct ReadyQueue {
+    ids: Mutex<VecDeque<usize>>,
+    thread: Thread,
+}
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<ReadyQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.ids.lock().unwrap().push_back(self.id);
+        self.ready.thread.unpark();
+    }
+}
+
+/// Single-threaded executor for `AsyncIo` actions which don't need to be `Send`.
+///
+/// Must be run on the thread which created it.
+pub struct LocalExecutor<'a> {
+    tasks: Vec<Option<Pin<Box<dyn Future<Output = ()> + 'a>>>>,
+    ready: Arc<ReadyQueue>,
+}
+
+impl<'a> LocalExecutor<'a> {
+    pub fn new() -> Self {
+        LocalExecutor {
+            tasks: Vec::new(),
+            ready: Arc::new(ReadyQueue {
+                ids: Mutex::new(VecDeque::new()),
+                thread: std::thread::current(),
+            }),
+        }
+    }
+
+    pub fn spawn<A>(&mut self, io: A)
+    where
+        A: AsyncIo<Output = ()> + 'a,
+    {
+        let id = self.tasks.len();
+        self.tasks.push(Some(Box::pin(io.run())));
+        self.ready.ids.lock().unwrap().push_back(id);
+    }
+
+    /// Run until every spawned task has completed.
+    pub fn run(&mut self) {
+        let mut remaining = self.tasks.iter().filter(|t| t.is_some()).count();
+
+        while remaining > 0 {
+            let next = self.ready.ids.lock().unwrap().pop_front();
+
+            let id = match next {
+                Some(id) => id,
+                None => {
+                    std::thread::park();
+                    continue;
+                }
+            };
+
+            // A stale wake-up may name a task which has finished, or one from an earlier run
+            if let Some(Some(task)) = self.tasks.get_mut(id) {
+                let waker = Waker::from(Arc::new(TaskWaker {
+                    id,
+                    ready: self.ready.clone(),
+                }));
+                let mut cx = Context::from_waker(&waker);
+
+                if task.as_mut().poll(&mut cx).is_ready() {
+                    self.tasks[id] = None;
+                    remaining -= 1;
+                }
+            }
+        }
+
+        self.tasks.clear();
+        self.ready.ids.lock().unwrap().clear();
+    }
+}
+
+impl Default for LocalExecutor<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Futures backed by helper threads
+
+struct Shared<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+fn complete<T>(shared: &Mutex<Shared<T>>, output: T) {
+    let mut shared = shared.lock().unwrap();
+    shared.output = Some(output);
+    if let Some(waker) = shared.waker.take() {
+        waker.wake();
+    }
+}
+
+/// Runs a closure on a new thread the first time it is polled.
+///
+/// A panic in the closure is re-raised by `poll`.
+struct Blocking<F, T> {
+    f: Option<F>,
+    shared: Arc<Mutex<Shared<std::thread::Result<T>>>>,
+}
+
+impl<F, T> Blocking<F, T> {
+    fn new(f: F) -> Self {
+        Blocking {
+            f: Some(f),
+            shared: Arc::new(Mutex::new(Shared {
+                output: None,
+                waker: None,
+            })),
+        }
+    }
+}
+
+impl<F, T> Unpin for Blocking<F, T> {}
+
+impl<F, T> Future for Blocking<F, T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap();
+        let output = shared.output.take();
+        if output.is_none() {
+            shared.waker = Some(cx.waker().clone());
+        }
+        drop(shared);
+
+        match output {
+            Some(Ok(output)) => return Poll::Ready(output),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {}
+        }
+
+        if let Some(f) = self.f.take() {
+            let shared = self.shared.clone();
+            std::thread::spawn(move || complete(&shared, panic::catch_unwind(AssertUnwindSafe(f))));
+        }
+
+        Poll::Pending
+    }
+}
+
+/// A timer waiting in the `TimerQueue`, earliest deadline first
+struct TimerEntry {
+    instant: std::time::Instant,
+    id: u64,
+    shared: std::sync::Weak<Mutex<Shared<()>>>,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        (self.instant, self.id) == (other.instant, other.id)
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (other.instant, other.id).cmp(&(self.instant, self.id))
+    }
+}
+
+#[derive(Default)]
+struct Timers {
+    heap: BinaryHeap<TimerEntry>,
+    next_id: u64,
+}
+
+#[derive(Default)]
+struct TimerQueue {
+    timers: Mutex<Timers>,
+    changed: Condvar,
+}
+
+/// Every `Timer` shares one thread, which sleeps until the earliest deadline.
+fn timer_queue() -> &'static TimerQueue {
+    static QUEUE: OnceLock<TimerQueue> = OnceLock::new();
+
+    QUEUE.get_or_init(|| {
+        std::thread::spawn(|| {
+            let queue = timer_queue();
+            let mut timers = queue.timers.lock().unwrap();
+            loop {
+                let now = std::time::Instant::now();
+                match timers.heap.peek() {
+                    Some(entry) if entry.instant <= now => {
+                        let entry = timers.heap.pop().unwrap();
+                        drop(timers);
+                        // Dropped timers have nobody left to wake
+                        if let Some(shared) = entry.shared.upgrade() {
+                            complete(&shared, ());
+                        }
+                        timers = queue.timers.lock().unwrap();
+                    }
+                    Some(entry) => {
+                        let wait = entry.instant - now;
+                        timers = queue.changed.wait_timeout(timers, wait).unwrap().0;
+                    }
+                    None => timers = queue.changed.wait(timers).unwrap(),
+                }
+            }
+        });
+        TimerQueue::default()
+    })
+}
+
+/// Completes once `instant` has passed, without blocking the executor.
+struct Timer {
+    instant: std::time::Instant,
+    shared: Option<Arc<Mutex<Shared<()>>>>,
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if std::time::Instant::now() >= self.instant {
+            return Poll::Ready(());
+        }
+
+        match &self.shared {
+            Some(shared) => {
+                let mut shared = shared.lock().unwrap();
+                if shared.output.take().is_some() {
+                    return Poll::Ready(());
+                }
+                shared.waker = Some(cx.waker().clone());
+            }
+            None => {
+                let shared = Arc::new(Mutex::new(Shared {
+                    output: None,
+                    waker: Some(cx.waker().clone()),
+                }));
+
+                let queue = timer_queue();
+                let mut timers = queue.timers.lock().unwrap();
+                timers.next_id += 1;
+                let id = timers.next_id;
+                timers.heap.push(TimerEntry {
+                    instant: self.instant,
+                    id,
+                    shared: Arc::downgrade(&shared),
+                });
+                queue.changed.notify_one();
+
+                self.shared = Some(shared);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+// Time
+
+#[derive(Clone)]
+pub struct DelayForIo {
+    duration: std::time::Duration,
+}
+
+impl AsyncIo for DelayForIo {
+    type Output = ();
+
+    async fn run(self) -> Self::Output {
+        Timer {
+            instant: std::time::Instant::now() + self.duration,
+            shared: None,
+        }
+        .await
+    }
+}
+
+pub fn delay_for(duration: std::time::Duration) -> DelayForIo {
+    DelayForIo { duration }
+}
+
+#[derive(Clone)]
+pub struct DelayUntilIo {
+    instant: std::time::Instant,
+}
+
+impl AsyncIo for DelayUntilIo {
+    type Output = ();
+
+    async fn run(self) -> Self::Output {
+        Timer {
+            instant: self.instant,
+            shared: None,
+        }
+        .await
+    }
+}
+
+pub fn delay_until(instant: std::time::Instant) -> DelayUntilIo {
+    DelayUntilIo { instant }
+}
+
+// Files
+
+pub fn read_file(path: PathBuf) -> FromIo<system::io::ReadFileIo> {
+    from_io(system::io::read_file(path))
+}
+
+pub fn try_read_file(path: PathBuf) -> FromIo<system::io::TryReadFileIo> {
+    from_io(system::io::try_read_file(path))
+}
+
+pub fn write_file(path: PathBuf, content: String) -> FromIo<system::io::WriteFileIo> {
+    from_io(system::io::write_file(path, content))
+}
+
+pub fn try_write_file(path: PathBuf, content: String) -> FromIo<system::io::TryWriteFileIo> {
+    from_io(system::io::try_write_file(path, content))
+}
+
+pub fn append_file(path: PathBuf, content: String) -> FromIo<system::io::AppendFileIo> {
+    from_io(system::io::append_file(path, content))
+}
+
+pub fn try_append_file(path: PathBuf, content: String) -> FromIo<system::io::TryAppendFileIo> {
+    from_io(system::io::try_append_file(path, content))
+}
+
+// Processes
+
+pub fn call_command(command: String) -> FromIo<system::process::CallCommandIo> {
+    from_io(system::process::call_command(command))
+}
+
+pub fn try_call_command(command: String) -> FromIo<system::process::TryCallCommandIo> {
+    from_io(system::process::try_call_command(command))
+}
+
+pub fn read_process(
+    exec: PathBuf,
+    args: Vec<String>,
+    stdin: String,
+) -> FromIo<system::process::ReadProcess> {
+    from_io(system::process::read_process(exec, args, stdin))
+}
+
+pub fn try_read_process(
+    exec: PathBuf,
+    args: Vec<String>,
+    stdin: String,
+) -> FromIo<system::process::TryReadProcess> {
+    from_io(system::process::try_read_process(exec, args, stdin))
+}
+
+pub fn read_create_process(
+    process: system::process::CreateProcess,
+    stdin: String,
+) -> FromIo<system::process::ReadCreateProcessIo> {
+    from_io(system::process::read_create_process(process, stdin))
+}
+
+pub fn try_read_create_process(
+    process: system::process::CreateProcess,
+    stdin: String,
+) -> FromIo<system::process::TryReadCreateProcessIo> {
+    from_io(system::process::try_read_create_process(process, stdin))
+}
+
+pub fn wait_for_process<P>(process: P) -> FromIo<system::process::WaitForProcess>
+where
+    P: Into<system::process::ProcessHandle>,
+{
+    from_io(system::process::wait_for_process(process))
+}
+
+pub fn try_wait_for_process<P>(process: P) -> FromIo<system::process::TryWaitForProcess>
+where
+    P: Into<system::process::ProcessHandle>,
+{
+    from_io(system::process::try_wait_for_process(process))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn test_block_on_combinators() {
+        let io = AsyncIoPure::<()>::pure(1)
+            .map(|x| x + 1)
+            .and_then(|x| AsyncIoPure::<()>::pure(x * 10));
+
+        assert_eq!(block_on(io.run()), 20);
+    }
+
+    #[test]
+    fn test_delay_for() {
+        let start = Instant::now();
+        delay_for(Duration::from_millis(20)).to_io().run();
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_from_io() {
+        let io = from_io(crate::prelude::from_result::<i32, ()>(Ok(1)));
+
+        assert_eq!(io.to_io().run(), Ok(1));
+    }
+
+    #[test]
+    fn test_local_executor_interleaves() {
+        let log = RefCell::new(Vec::new());
+        let mut executor = LocalExecutor::new();
+
+        let log_after = |ms: u64, x: i32| {
+            let log = &log;
+            delay_for(Duration::from_millis(ms)).map(move |_| log.borrow_mut().push(x))
+        };
+
+        // Run one after the other these would take 550ms
+        let start = Instant::now();
+        executor.spawn(log_after(300, 1));
+        executor.spawn(log_after(250, 2));
+        executor.run();
+
+        assert_eq!(*log.borrow(), vec![2, 1]);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_many_timers() {
+        let count = RefCell::new(0);
+        let mut executor = LocalExecutor::new();
+
+        for ms in (0..200).rev() {
+            let count = &count;
+            executor.spawn(
+                delay_for(Duration::from_millis(ms % 20)).map(move |_| *count.borrow_mut() += 1),
+            );
+        }
+        executor.run();
+
+        assert_eq!(*count.borrow(), 200);
+    }
+
+    #[test]
+    fn test_from_io_reraises_panics() {
+        let missing = std::env::temp_dir().join(format!("entoli-{}-missing", std::process::id()));
+
+        let result = std::panic::catch_unwind(|| read_file(missing).to_io().run());
+
+        assert!(result.is_err());
+    }
+
+    /// Wakes itself twice before finishing, leaving a stale id in the ready queue
+    struct WakeTwice {
+        polled: bool,
+    }
+
+    impl Future for WakeTwice {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.polled {
+                return Poll::Ready(());
+            }
+            self.polled = true;
+            cx.waker().wake_by_ref();
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    impl AsyncIo for WakeTwice {
+        type Output = ();
+
+        fn run(self) -> impl Future<Output = ()> {
+            self
+        }
+    }
+
+    #[test]
+    fn test_local_executor_reuse() {
+        let count = RefCell::new(0);
+        let mut executor = LocalExecutor::new();
+
+        executor.spawn(WakeTwice { polled: false });
+        executor.spawn(WakeTwice { polled: false });
+        executor.run();
+
+        executor.spawn(AsyncIoPure::<()>::pure(()).map(|_| *count.borrow_mut() += 1));
+        executor.run();
+
+        assert_eq!(*count.borrow(), 1);
+    }
+}
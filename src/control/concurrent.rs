@@ -1,4 +1,12 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+use crate::data::either::Either;
 use crate::prelude::Io;
+use crate::system::runner::{run_with_runner, thread_runner};
 
 #[derive(Clone)]
 pub struct RecIo<I> {
@@ -12,12 +20,13 @@ where
     type Output = ();
 
     fn run(self) -> Self::Output {
-        loop {
+        while !current_thread_killed() {
             self.inner.clone().run()
         }
     }
 }
 
+/// Repeat `io` until the current thread is killed with `kill_thread`.
 pub fn rec<I>(io: I) -> RecIo<I>
 where
     I: Io<Output = ()> + Clone,
@@ -34,10 +43,11 @@ impl Io for DelayForIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::thread::sleep(self.duration);
+        sleep_until(std::time::Instant::now() + self.duration);
     }
 }
 
+/// Sleep for `duration`, waking early if the current thread is killed.
 pub fn delay_for(duration: std::time::Duration) -> DelayForIo {
     DelayForIo { duration }
 }
@@ -51,13 +61,1181 @@ impl Io for DelayUntilIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        let now = std::time::Instant::now();
-        if now < self.instant {
-            std::thread::sleep(self.instant - now);
-        }
+        sleep_until(self.instant);
     }
 }
 
+/// Sleep until `instant`, waking early if the current thread is killed.
 pub fn delay_until(instant: std::time::Instant) -> DelayUntilIo {
     DelayUntilIo { instant }
 }
+
+// Cancellation
+
+/// How often blocked waits re-check their cancel token.
+pub(crate) const CANCEL_POLL: std::time::Duration = std::time::Duration::from_millis(5);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    deadline: Option<std::time::Instant>,
+    parents: Vec<CancelToken>,
+}
+
+/// Shared flag used to ask running `Io` actions to stop.
+///
+/// A token is also cancelled once any token it was derived from with `child` is.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    state: Arc<CancelState>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_parents(parents: Vec<CancelToken>, deadline: Option<std::time::Instant>) -> Self {
+        CancelToken {
+            state: Arc::new(CancelState {
+                cancelled: AtomicBool::new(false),
+                deadline,
+                parents,
+            }),
+        }
+    }
+
+    pub fn child(&self) -> Self {
+        Self::with_parents(vec![self.clone()], None)
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+            || self
+                .state
+                .deadline
+                .is_some_and(|deadline| std::time::Instant::now() >= deadline)
+            || self.state.parents.iter().any(CancelToken::is_cancelled)
+    }
+
+    /// The earliest time by which `timeout` cancels this token, if it runs under one
+    #[cfg(feature = "http_client")]
+    fn deadline(&self) -> Option<std::time::Instant> {
+        self.state
+            .parents
+            .iter()
+            .filter_map(CancelToken::deadline)
+            .chain(self.state.deadline)
+            .min()
+    }
+}
+
+fn sleep_until(instant: std::time::Instant) {
+    loop {
+        let now = std::time::Instant::now();
+        if now >= instant || current_thread_killed() {
+            return;
+        }
+        std::thread::sleep(CANCEL_POLL.min(instant - now));
+    }
+}
+
+// Threads
+
+/// Identifies a thread started with `fork_io`, and lets other threads ask it to stop.
+///
+/// Killing is cooperative: it cancels the thread's token, which `rec`, the delays and
+/// `is_killed` observe.
+#[derive(Clone, Debug)]
+pub struct ThreadId {
+    id: std::thread::ThreadId,
+    token: CancelToken,
+}
+
+impl ThreadId {
+    pub fn cancel_token(&self) -> CancelToken {
+        self.token.clone()
+    }
+}
+
+impl PartialEq for ThreadId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ThreadId {}
+
+thread_local! {
+    static CURRENT_THREAD: RefCell<Option<ThreadId>> = const { RefCell::new(None) };
+}
+
+fn current_thread_id() -> ThreadId {
+    CURRENT_THREAD.with(|current| {
+        current
+            .borrow_mut()
+            .get_or_insert_with(|| ThreadId {
+                id: std::thread::current().id(),
+                token: CancelToken::new(),
+            })
+            .clone()
+    })
+}
+
+pub(crate) fn current_thread_killed() -> bool {
+    CURRENT_THREAD.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(|tid| tid.token.is_cancelled())
+    })
+}
+
+/// When the current thread will be killed by an enclosing `timeout`, if it runs under one
+#[cfg(feature = "http_client")]
+pub(crate) fn current_thread_deadline() -> Option<std::time::Instant> {
+    CURRENT_THREAD.with(|current| current.borrow().as_ref()?.token.deadline())
+}
+
+/// The error returned by fallible actions which stop because their thread was killed
+#[cfg(feature = "websocket")]
+pub(crate) fn killed_error() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, "thread was killed")
+}
+
+struct RestoreThreadId(Option<ThreadId>);
+
+impl Drop for RestoreThreadId {
+    fn drop(&mut self) {
+        CURRENT_THREAD.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Run `f` on this thread as if it had been forked with `token`.
+fn with_thread_token<T>(token: CancelToken, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT_THREAD.with(|current| {
+        current.replace(Some(ThreadId {
+            id: std::thread::current().id(),
+            token,
+        }))
+    });
+    let _restore = RestoreThreadId(previous);
+
+    f()
+}
+
+/// Handle returned by `fork_io`, used to wait for the thread's output.
+pub struct ThreadHandle<T> {
+    thread_id: ThreadId,
+    handle: std::thread::JoinHandle<T>,
+}
+
+impl<T> ThreadHandle<T> {
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id.clone()
+    }
+}
+
+fn spawn_thread<T, F>(token: CancelToken, f: F) -> ThreadHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let thread_token = token.clone();
+    let runner = thread_runner();
+
+    let handle = std::thread::spawn(move || {
+        CURRENT_THREAD.with(|current| {
+            *current.borrow_mut() = Some(ThreadId {
+                id: std::thread::current().id(),
+                token: thread_token,
+            })
+        });
+        run_with_runner(runner, f)
+    });
+
+    ThreadHandle {
+        thread_id: ThreadId {
+            id: handle.thread().id(),
+            token,
+        },
+        handle,
+    }
+}
+
+#[derive(Clone)]
+pub struct ForkIo<I> {
+    io: I,
+}
+
+impl<I> Io for ForkIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    type Output = ThreadHandle<I::Output>;
+
+    fn run(self) -> Self::Output {
+        let io = self.io;
+        spawn_thread(CancelToken::new(), move || io.run())
+    }
+}
+
+pub fn fork_io<I>(io: I) -> ForkIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    ForkIo { io }
+}
+
+pub struct WaitThreadIo<T> {
+    handle: ThreadHandle<T>,
+}
+
+impl<T> Io for WaitThreadIo<T> {
+    type Output = std::thread::Result<T>;
+
+    fn run(self) -> Self::Output {
+        self.handle.handle.join()
+    }
+}
+
+pub fn wait_thread<T>(handle: ThreadHandle<T>) -> WaitThreadIo<T> {
+    WaitThreadIo { handle }
+}
+
+#[derive(Clone)]
+pub struct KillThreadIo {
+    thread_id: ThreadId,
+}
+
+impl Io for KillThreadIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        self.thread_id.token.cancel();
+    }
+}
+
+pub fn kill_thread(thread_id: ThreadId) -> KillThreadIo {
+    KillThreadIo { thread_id }
+}
+
+#[derive(Clone)]
+#[allow(non_camel_case_types)]
+pub struct my_thread_id;
+
+impl Io for my_thread_id {
+    type Output = ThreadId;
+
+    fn run(self) -> Self::Output {
+        current_thread_id()
+    }
+}
+
+#[derive(Clone)]
+#[allow(non_camel_case_types)]
+pub struct is_killed;
+
+impl Io for is_killed {
+    type Output = bool;
+
+    fn run(self) -> Self::Output {
+        current_thread_killed()
+    }
+}
+
+// Timeouts
+
+fn run_interruptible<I>(
+    io: I,
+    token: Option<CancelToken>,
+    deadline: Option<std::time::Instant>,
+) -> Option<I::Output>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    let mut parents = vec![current_thread_id().token];
+    parents.extend(token);
+    let helper = CancelToken::with_parents(parents, deadline);
+
+    let (tx, rx) = mpsc::channel();
+    spawn_thread(helper.clone(), move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| io.run()));
+        let _ = tx.send(result);
+    });
+
+    loop {
+        let now = std::time::Instant::now();
+        if helper.is_cancelled() || deadline.is_some_and(|deadline| now >= deadline) {
+            helper.cancel();
+            return None;
+        }
+
+        let wait = deadline.map_or(CANCEL_POLL, |deadline| CANCEL_POLL.min(deadline - now));
+        match rx.recv_timeout(wait) {
+            Ok(Ok(output)) => return Some(output),
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimeoutIo<I> {
+    duration: std::time::Duration,
+    io: I,
+}
+
+impl<I> Io for TimeoutIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    type Output = Option<I::Output>;
+
+    fn run(self) -> Self::Output {
+        let deadline = std::time::Instant::now() + self.duration;
+        run_interruptible(self.io, None, Some(deadline))
+    }
+}
+
+/// Run `io`, giving up with `None` if it hasn't finished after `duration`.
+///
+/// `io` runs on a helper thread whose token is then cancelled. Only some actions observe it: `rec`,
+/// the delays, `is_killed`, waiting on a child process, which kills the child, and `ws_send` and
+/// `ws_recv`, which fail with `ErrorKind::Interrupted`. `http_request` is given the time left as its
+/// request timeout, so it fails at the deadline too. Anything else is abandoned and finishes in
+/// the background, along with its helper thread.
+pub fn timeout<I>(duration: std::time::Duration, io: I) -> TimeoutIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    TimeoutIo { duration, io }
+}
+
+#[derive(Clone)]
+pub struct CancellableIo<I> {
+    token: CancelToken,
+    io: I,
+}
+
+impl<I> Io for CancellableIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    type Output = Option<I::Output>;
+
+    fn run(self) -> Self::Output {
+        run_interruptible(self.io, Some(self.token), None)
+    }
+}
+
+/// Run `io`, giving up with `None` as soon as `token` is cancelled from another thread.
+///
+/// Interrupts the same actions as `timeout`, except `http_request`, which has no deadline to go by
+/// and is abandoned.
+pub fn cancellable<I>(token: CancelToken, io: I) -> CancellableIo<I>
+where
+    I: Io + Send + 'static,
+    I::Output: Send + 'static,
+{
+    CancellableIo { token, io }
+}
+
+// MVar
+
+/// Shared box which is either empty or full, as Haskell's `MVar`.
+pub struct MVar<T> {
+    inner: Arc<(Mutex<Option<T>>, Condvar)>,
+}
+
+impl<T> Clone for MVar<T> {
+    fn clone(&self) -> Self {
+        MVar {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> MVar<T> {
+    fn take(&self) -> T {
+        let (lock, cvar) = &*self.inner;
+        let mut value = cvar
+            .wait_while(lock.lock().unwrap(), |v| v.is_none())
+            .unwrap();
+        let t = value.take().unwrap();
+        cvar.notify_all();
+        t
+    }
+
+    fn put(&self, t: T) {
+        let (lock, cvar) = &*self.inner;
+        let mut value = cvar
+            .wait_while(lock.lock().unwrap(), |v| v.is_some())
+            .unwrap();
+        *value = Some(t);
+        cvar.notify_all();
+    }
+}
+
+/// Holds the value taken by `modify_mvar`, putting it back if the modification panics
+struct RestoreMVar<'a, T> {
+    mvar: &'a MVar<T>,
+    value: Option<T>,
+}
+
+impl<T> Drop for RestoreMVar<'_, T> {
+    fn drop(&mut self) {
+        if let Some(t) = self.value.take() {
+            let (lock, cvar) = &*self.mvar.inner;
+            let mut value = lock.lock().unwrap_or_else(|e| e.into_inner());
+            // Never block while unwinding; a value put meanwhile wins
+            if value.is_none() {
+                *value = Some(t);
+                cvar.notify_all();
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NewMVarIo<T> {
+    value: Option<T>,
+}
+
+impl<T> Io for NewMVarIo<T> {
+    type Output = MVar<T>;
+
+    fn run(self) -> Self::Output {
+        MVar {
+            inner: Arc::new((Mutex::new(self.value), Condvar::new())),
+        }
+    }
+}
+
+pub fn new_mvar<T>(t: T) -> NewMVarIo<T> {
+    NewMVarIo { value: Some(t) }
+}
+
+pub fn new_empty_mvar<T>() -> NewMVarIo<T> {
+    NewMVarIo { value: None }
+}
+
+#[derive(Clone)]
+pub struct TakeMVarIo<T> {
+    mvar: MVar<T>,
+}
+
+impl<T> Io for TakeMVarIo<T> {
+    type Output = T;
+
+    fn run(self) -> Self::Output {
+        self.mvar.take()
+    }
+}
+
+pub fn take_mvar<T>(mvar: MVar<T>) -> TakeMVarIo<T> {
+    TakeMVarIo { mvar }
+}
+
+#[derive(Clone)]
+pub struct PutMVarIo<T> {
+    mvar: MVar<T>,
+    value: T,
+}
+
+impl<T> Io for PutMVarIo<T> {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        self.mvar.put(self.value)
+    }
+}
+
+pub fn put_mvar<T>(mvar: MVar<T>, value: T) -> PutMVarIo<T> {
+    PutMVarIo { mvar, value }
+}
+
+#[derive(Clone)]
+pub struct ReadMVarIo<T> {
+    mvar: MVar<T>,
+}
+
+impl<T> Io for ReadMVarIo<T>
+where
+    T: Clone,
+{
+    type Output = T;
+
+    fn run(self) -> Self::Output {
+        let (lock, cvar) = &*self.mvar.inner;
+        let value = cvar
+            .wait_while(lock.lock().unwrap(), |v| v.is_none())
+            .unwrap();
+        value.clone().unwrap()
+    }
+}
+
+pub fn read_mvar<T>(mvar: MVar<T>) -> ReadMVarIo<T>
+where
+    T: Clone,
+{
+    ReadMVarIo { mvar }
+}
+
+#[derive(Clone)]
+pub struct ModifyMVarIo<T, F> {
+    mvar: MVar<T>,
+    f: F,
+}
+
+impl<T, B, F, I> Io for ModifyMVarIo<T, F>
+where
+    F: FnOnce(&mut T) -> I,
+    I: Io<Output = B>,
+{
+    type Output = B;
+
+    fn run(self) -> Self::Output {
+        let mut taken = RestoreMVar {
+            mvar: &self.mvar,
+            value: Some(self.mvar.take()),
+        };
+
+        let b = (self.f)(taken.value.as_mut().unwrap()).run();
+        self.mvar.put(taken.value.take().unwrap());
+        b
+    }
+}
+
+/// Take the value, let `f` change it in place, run the action `f` returns, then put the value
+/// back and return the action's output.
+///
+/// The `MVar` stays empty until the action is done. If `f` or its action panics the value is put
+/// back as `f` left it, so other threads don't block on an empty `MVar`.
+pub fn modify_mvar<T, B, F, I>(mvar: MVar<T>, f: F) -> ModifyMVarIo<T, F>
+where
+    F: FnOnce(&mut T) -> I,
+    I: Io<Output = B>,
+{
+    ModifyMVarIo { mvar, f }
+}
+
+#[derive(Clone)]
+pub struct ModifyMVarIo_<T, F> {
+    mvar: MVar<T>,
+    f: F,
+}
+
+impl<T, F, I> Io for ModifyMVarIo_<T, F>
+where
+    F: FnOnce(&mut T) -> I,
+    I: Io<Output = ()>,
+{
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        modify_mvar(self.mvar, self.f).run()
+    }
+}
+
+pub fn modify_mvar_<T, F, I>(mvar: MVar<T>, f: F) -> ModifyMVarIo_<T, F>
+where
+    F: FnOnce(&mut T) -> I,
+    I: Io<Output = ()>,
+{
+    ModifyMVarIo_ { mvar, f }
+}
+
+// Chan
+
+struct ChanState<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: Option<usize>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+/// FIFO channel shared between any number of readers and writers.
+///
+/// Unbounded unless created with `new_bounded_chan`, in which case writers block while it is full.
+pub struct Chan<T> {
+    state: Arc<ChanState<T>>,
+}
+
+impl<T> Clone for Chan<T> {
+    fn clone(&self) -> Self {
+        Chan {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Chan<T> {
+    fn write(&self, t: T) {
+        let state = &*self.state;
+        let mut queue = state
+            .not_full
+            .wait_while(state.queue.lock().unwrap(), |q| {
+                state.capacity.is_some_and(|cap| q.len() >= cap)
+            })
+            .unwrap();
+        queue.push_back(t);
+        state.not_empty.notify_one();
+    }
+
+    fn read(&self) -> T {
+        let state = &*self.state;
+        let mut queue = state
+            .not_empty
+            .wait_while(state.queue.lock().unwrap(), |q| q.is_empty())
+            .unwrap();
+        let t = queue.pop_front().unwrap();
+        state.not_full.notify_one();
+        t
+    }
+}
+
+pub struct NewChanIo<T> {
+    capacity: Option<usize>,
+    _t: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for NewChanIo<T> {
+    fn clone(&self) -> Self {
+        NewChanIo {
+            capacity: self.capacity,
+            _t: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> Io for NewChanIo<T> {
+    type Output = Chan<T>;
+
+    fn run(self) -> Self::Output {
+        Chan {
+            state: Arc::new(ChanState {
+                queue: Mutex::new(VecDeque::new()),
+                capacity: self.capacity,
+                not_empty: Condvar::new(),
+                not_full: Condvar::new(),
+            }),
+        }
+    }
+}
+
+pub fn new_chan<T>() -> NewChanIo<T> {
+    NewChanIo {
+        capacity: None,
+        _t: std::marker::PhantomData,
+    }
+}
+
+pub fn new_bounded_chan<T>(capacity: usize) -> NewChanIo<T> {
+    assert!(capacity > 0, "bounded channel needs a positive capacity");
+    NewChanIo {
+        capacity: Some(capacity),
+        _t: std::marker::PhantomData,
+    }
+}
+
+#[derive(Clone)]
+pub struct WriteChanIo<T> {
+    chan: Chan<T>,
+    value: T,
+}
+
+impl<T> Io for WriteChanIo<T> {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        self.chan.write(self.value)
+    }
+}
+
+pub fn write_chan<T>(chan: Chan<T>, value: T) -> WriteChanIo<T> {
+    WriteChanIo { chan, value }
+}
+
+#[derive(Clone)]
+pub struct WriteListToChanIo<T> {
+    chan: Chan<T>,
+    values: Vec<T>,
+}
+
+impl<T> Io for WriteListToChanIo<T> {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        for value in self.values {
+            self.chan.write(value);
+        }
+    }
+}
+
+pub fn write_list_to_chan<T>(chan: Chan<T>, values: Vec<T>) -> WriteListToChanIo<T> {
+    WriteListToChanIo { chan, values }
+}
+
+#[derive(Clone)]
+pub struct ReadChanIo<T> {
+    chan: Chan<T>,
+}
+
+impl<T> Io for ReadChanIo<T> {
+    type Output = T;
+
+    fn run(self) -> Self::Output {
+        self.chan.read()
+    }
+}
+
+pub fn read_chan<T>(chan: Chan<T>) -> ReadChanIo<T> {
+    ReadChanIo { chan }
+}
+
+// Async
+
+#[derive(Clone)]
+pub struct ConcurrentlyIo<A, B> {
+    lhs: A,
+    rhs: B,
+}
+
+impl<A, B> Io for ConcurrentlyIo<A, B>
+where
+    A: Io + Send + 'static,
+    A::Output: Send + 'static,
+    B: Io,
+{
+    type Output = (A::Output, B::Output);
+
+    fn run(self) -> Self::Output {
+        let token = current_thread_id().token;
+        let rhs_token = token.child();
+
+        let (lhs, lhs_failed) = (self.lhs, rhs_token.clone());
+        let handle = spawn_thread(token.child(), move || {
+            match panic::catch_unwind(AssertUnwindSafe(|| lhs.run())) {
+                Ok(a) => a,
+                Err(payload) => {
+                    lhs_failed.cancel();
+                    panic::resume_unwind(payload)
+                }
+            }
+        });
+
+        let rhs = self.rhs;
+        let b = match panic::catch_unwind(AssertUnwindSafe(|| {
+            with_thread_token(rhs_token, || rhs.run())
+        })) {
+            Ok(b) => b,
+            Err(payload) => {
+                kill_thread(handle.thread_id()).run();
+                let _ = handle.handle.join();
+                panic::resume_unwind(payload)
+            }
+        };
+
+        // If `lhs` failed, `b` may come from an `rhs` cut short and is dropped
+        match handle.handle.join() {
+            Ok(a) => (a, b),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Run both actions at the same time and wait for both outputs.
+///
+/// `lhs` runs on a new thread. If either side panics the other is killed with `kill_thread`, and
+/// the panic is re-raised here once both have stopped.
+pub fn concurrently<A, B>(lhs: A, rhs: B) -> ConcurrentlyIo<A, B>
+where
+    A: Io + Send + 'static,
+    A::Output: Send + 'static,
+    B: Io,
+{
+    ConcurrentlyIo { lhs, rhs }
+}
+
+#[derive(Clone)]
+pub struct RaceIo<A, B> {
+    lhs: A,
+    rhs: B,
+}
+
+impl<A, B> Io for RaceIo<A, B>
+where
+    A: Io + Send + 'static,
+    A::Output: Send + 'static,
+    B: Io + Send + 'static,
+    B::Output: Send + 'static,
+{
+    type Output = Either<A::Output, B::Output>;
+
+    fn run(self) -> Self::Output {
+        let (tx, rx) = mpsc::channel();
+        let token = current_thread_id().token;
+
+        let (lhs, lhs_tx) = (self.lhs, tx.clone());
+        let lhs_handle = spawn_thread(token.child(), move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| lhs.run()));
+            let _ = lhs_tx.send(result.map(Either::Left));
+        });
+
+        let rhs = self.rhs;
+        let rhs_handle = spawn_thread(token.child(), move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| rhs.run()));
+            let _ = tx.send(result.map(Either::Right));
+        });
+
+        let winner = rx.recv().unwrap();
+        kill_thread(lhs_handle.thread_id()).run();
+        kill_thread(rhs_handle.thread_id()).run();
+
+        match winner {
+            Ok(output) => output,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Run both actions at the same time and return the output of whichever finishes first.
+///
+/// The loser is killed with `kill_thread` and left to stop on its own.
+pub fn race<A, B>(lhs: A, rhs: B) -> RaceIo<A, B>
+where
+    A: Io + Send + 'static,
+    A::Output: Send + 'static,
+    B: Io + Send + 'static,
+    B::Output: Send + 'static,
+{
+    RaceIo { lhs, rhs }
+}
+
+#[derive(Clone)]
+pub struct MapConcurrentlyIo<T, F> {
+    limit: usize,
+    xs: Vec<T>,
+    f: F,
+}
+
+impl<T, F, I> Io for MapConcurrentlyIo<T, F>
+where
+    T: Send,
+    F: Fn(T) -> I + Sync,
+    I: Io,
+    I::Output: Send,
+{
+    type Output = Vec<I::Output>;
+
+    fn run(self) -> Self::Output {
+        let len = self.xs.len();
+        let queue = Mutex::new(self.xs.into_iter().enumerate());
+        let outputs = Mutex::new((0..len).map(|_| None).collect::<Vec<_>>());
+        let failed = current_thread_id().token.child();
+        let runner = thread_runner();
+        let f = &self.f;
+
+        let panics = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.limit.min(len))
+                .map(|_| {
+                    let (token, runner) = (failed.child(), runner.clone());
+                    scope.spawn(|| {
+                        run_with_runner(runner, || {
+                            with_thread_token(token, || {
+                                while !failed.is_cancelled() {
+                                    let next = queue.lock().unwrap().next();
+                                    let Some((i, x)) = next else { break };
+
+                                    match panic::catch_unwind(AssertUnwindSafe(|| f(x).run())) {
+                                        Ok(output) => outputs.lock().unwrap()[i] = Some(output),
+                                        Err(payload) => {
+                                            failed.cancel();
+                                            panic::resume_unwind(payload)
+                                        }
+                                    }
+                                }
+                            })
+                        })
+                    })
+                })
+                .collect();
+
+            // Joined here so the worker's own payload is kept, not the scope's
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().err())
+                .collect::<Vec<_>>()
+        });
+
+        if let Some(payload) = panics.into_iter().next() {
+            panic::resume_unwind(payload);
+        }
+
+        let outputs = outputs.into_inner().unwrap();
+        if outputs.iter().any(Option::is_none) {
+            // Only a kill from outside stops the workers without a panic of their own
+            panic!("map_concurrently was killed before every element finished");
+        }
+
+        outputs.into_iter().flatten().collect()
+    }
+}
+
+/// Run `f` over every element with at most `limit` actions in flight, keeping the input order.
+///
+/// Once one action panics no further elements are started, the actions still running are killed
+/// with `kill_thread`, and the panic is re-raised. If the calling thread is killed, no further
+/// elements are started either and this panics once the running actions have stopped.
+///
+/// # Panics
+///
+/// If `limit` is zero.
+pub fn map_concurrently<T, F, I>(limit: usize, xs: Vec<T>, f: F) -> MapConcurrentlyIo<T, F>
+where
+    T: Send,
+    F: Fn(T) -> I + Sync,
+    I: Io,
+    I::Output: Send,
+{
+    assert!(limit > 0, "map_concurrently needs a positive limit");
+    MapConcurrentlyIo { limit, xs, f }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::prelude::IoPure;
+
+    #[test]
+    fn test_fork_io_wait_thread() {
+        let handle = fork_io(new_mvar(1).and_then(|m| take_mvar(m).map(|x| x + 1))).run();
+
+        assert_eq!(wait_thread(handle).run().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_kill_thread_stops_rec() {
+        let handle = fork_io(rec(delay_for(Duration::from_millis(1)))).run();
+        kill_thread(handle.thread_id()).run();
+
+        assert!(wait_thread(handle).run().is_ok());
+    }
+
+    #[test]
+    fn test_mvar_handoff() {
+        let mvar = new_empty_mvar().run();
+        let handle = fork_io(put_mvar(mvar.clone(), 42)).run();
+
+        assert_eq!(take_mvar(mvar.clone()).run(), 42);
+        wait_thread(handle).run().unwrap();
+
+        put_mvar(mvar.clone(), 1).run();
+        let old = modify_mvar(mvar.clone(), |x| {
+            let old = *x;
+            *x += 1;
+            IoPure::<()>::pure(old)
+        })
+        .run();
+        modify_mvar_(mvar.clone(), |x| {
+            *x *= 10;
+            IoPure::<()>::pure(())
+        })
+        .run();
+
+        assert_eq!(old, 1);
+        assert_eq!(read_mvar(mvar).run(), 20);
+    }
+
+    #[test]
+    fn test_modify_mvar_restores_on_panic() {
+        // Not Clone, so the value can only come back from the guard
+        struct Log(Vec<i32>);
+
+        let mvar = new_mvar(Log(vec![1])).run();
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            modify_mvar_(mvar.clone(), |log: &mut Log| {
+                log.0.push(2);
+                IoPure::<()>::pure(()).map(|_| panic!("boom"))
+            })
+            .run()
+        }));
+
+        assert!(panicked.is_err());
+        assert_eq!(take_mvar(mvar).run().0, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_bounded_chan() {
+        let chan = new_bounded_chan(1).run();
+        let handle = fork_io(write_list_to_chan(chan.clone(), vec![1, 2, 3])).run();
+
+        let xs: Vec<i32> = (0..3).map(|_| read_chan(chan.clone()).run()).collect();
+        wait_thread(handle).run().unwrap();
+
+        assert_eq!(xs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_concurrently() {
+        let start = std::time::Instant::now();
+        let io = concurrently(
+            delay_for(Duration::from_millis(200)).map(|_| 1),
+            delay_for(Duration::from_millis(200)).map(|_| "b"),
+        );
+
+        assert_eq!(io.run(), (1, "b"));
+        assert!(start.elapsed() < Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_race_kills_loser() {
+        let io = race(
+            delay_for(Duration::from_millis(10)).map(|_| 1),
+            rec(delay_for(Duration::from_millis(1))),
+        );
+
+        assert_eq!(io.run(), Either::Left(1));
+    }
+
+    #[test]
+    fn test_map_concurrently() {
+        let io = map_concurrently(2, vec![30, 10, 20], |ms| {
+            delay_for(Duration::from_millis(ms)).map(move |_| ms * 2)
+        });
+
+        assert_eq!(io.run(), vec![60, 20, 40]);
+    }
+
+    #[test]
+    fn test_concurrently_kills_other_side_on_panic() {
+        let start = std::time::Instant::now();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            concurrently(
+                IoPure::<()>::pure(()).map(|_| -> () { panic!("lhs failed") }),
+                delay_for(Duration::from_secs(60)),
+            )
+            .run()
+        }));
+
+        assert_eq!(
+            panicked.unwrap_err().downcast_ref::<&str>(),
+            Some(&"lhs failed")
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(!is_killed.run());
+    }
+
+    #[test]
+    fn test_map_concurrently_reraises_payload() {
+        let start = std::time::Instant::now();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            map_concurrently(2, vec![0, 60_000], |ms| {
+                delay_for(Duration::from_millis(ms)).map(move |_| {
+                    assert_ne!(ms, 0, "element {} failed", ms);
+                })
+            })
+            .run()
+        }));
+
+        let payload = panicked.unwrap_err();
+        assert!(payload
+            .downcast_ref::<String>()
+            .is_some_and(|message| message.contains("element 0 failed")));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let slow = timeout(
+            Duration::from_millis(10),
+            rec(delay_for(Duration::from_millis(1))),
+        );
+        let fast = timeout(Duration::from_secs(1), delay_for(Duration::from_millis(1)));
+
+        assert_eq!(slow.run(), None);
+        assert_eq!(fast.run(), Some(()));
+    }
+
+    #[test]
+    fn test_cancellable() {
+        let token = CancelToken::new();
+        let handle = fork_io(cancellable(
+            token.clone(),
+            delay_for(Duration::from_secs(60)),
+        ))
+        .run();
+        token.cancel();
+
+        assert_eq!(wait_thread(handle).run().unwrap(), None);
+    }
+
+    #[test]
+    fn test_kill_interrupts_delay() {
+        let start = std::time::Instant::now();
+        let handle = fork_io(delay_for(Duration::from_secs(60))).run();
+        kill_thread(handle.thread_id()).run();
+        wait_thread(handle).run().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_concurrently_joins_lhs_before_reraising() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let lhs_stopped = stopped.clone();
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            concurrently(
+                // Keeps running for a while after the kill wakes it
+                delay_for(Duration::from_secs(60)).map(move |_| {
+                    std::thread::sleep(Duration::from_millis(50));
+                    lhs_stopped.store(true, Ordering::SeqCst)
+                }),
+                IoPure::<()>::pure(()).map(|_| -> () { panic!("rhs failed") }),
+            )
+            .run()
+        }));
+
+        assert!(panicked.is_err());
+        assert!(stopped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    #[should_panic(expected = "map_concurrently needs a positive limit")]
+    fn test_map_concurrently_rejects_zero_limit() {
+        map_concurrently(0, vec![1], IoPure::<()>::pure);
+    }
+
+    #[test]
+    fn test_map_concurrently_killed() {
+        let token = CancelToken::new();
+        token.cancel();
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_thread_token(token, || {
+                map_concurrently(2, vec![1, 2, 3], IoPure::<()>::pure).run()
+            })
+        }));
+
+        assert!(panicked
+            .unwrap_err()
+            .downcast_ref::<&str>()
+            .is_some_and(|message| message.contains("killed before every element finished")));
+    }
+}
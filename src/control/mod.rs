@@ -1 +1,3 @@
+pub mod async_io;
 pub mod concurrent;
+pub mod stm;
@@ -0,0 +1,605 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::prelude::Io;
+
+// Transactions
+
+/// Why a transaction stopped before committing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Abort {
+    /// The transaction asked to be run again once some `TVar` changes.
+    Retry,
+    /// A `TVar` read by the transaction changed underneath it.
+    Conflict,
+}
+
+pub type StmResult<T> = Result<T, Abort>;
+
+/// Counts committed transactions, and guards the commit of each one.
+static COMMITS: Mutex<u64> = Mutex::new(0);
+static COMMITTED: Condvar = Condvar::new();
+
+static NEXT_TVAR_ID: AtomicUsize = AtomicUsize::new(0);
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+trait TVarCell: Send + Sync {
+    fn version(&self) -> u64;
+
+    fn commit(&self, value: Value);
+}
+
+/// Read and write log of a running transaction.
+pub struct Transaction {
+    reads: HashMap<usize, (u64, Arc<dyn TVarCell>)>,
+    writes: HashMap<usize, (Value, Arc<dyn TVarCell>)>,
+}
+
+impl Transaction {
+    fn new() -> Self {
+        Transaction {
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+        }
+    }
+
+    fn read<T>(&mut self, tvar: &TVar<T>) -> StmResult<Arc<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        if let Some((value, _)) = self.writes.get(&tvar.id) {
+            return Ok(value.clone().downcast::<T>().unwrap());
+        }
+
+        // Holding the commit lock means no transaction commits halfway through the validation
+        let _commits = COMMITS.lock().unwrap();
+
+        let (version, value) = {
+            let state = tvar.cell.state.lock().unwrap();
+            (state.0, state.1.clone())
+        };
+
+        // Every earlier read must still be current, so the transaction never sees a mix of
+        // values from before and after another commit
+        if !self.is_valid() {
+            return Err(Abort::Conflict);
+        }
+
+        match self.reads.get(&tvar.id) {
+            Some((seen, _)) if *seen != version => Err(Abort::Conflict),
+            Some(_) => Ok(value),
+            None => {
+                self.reads.insert(tvar.id, (version, tvar.cell.clone()));
+                Ok(value)
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.reads
+            .values()
+            .all(|(version, cell)| cell.version() == *version)
+    }
+
+    fn write<T>(&mut self, tvar: &TVar<T>, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.writes
+            .insert(tvar.id, (Arc::new(value), tvar.cell.clone()));
+    }
+
+    fn commit(self) -> bool {
+        let mut commits = COMMITS.lock().unwrap();
+
+        let valid = self.is_valid();
+
+        if valid && !self.writes.is_empty() {
+            for (value, cell) in self.writes.into_values() {
+                cell.commit(value);
+            }
+            *commits += 1;
+            COMMITTED.notify_all();
+        }
+
+        valid
+    }
+}
+
+// TVar
+
+struct Cell<T> {
+    state: Mutex<(u64, Arc<T>)>,
+}
+
+impl<T> TVarCell for Cell<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn version(&self) -> u64 {
+        self.state.lock().unwrap().0
+    }
+
+    fn commit(&self, value: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += 1;
+        state.1 = value.downcast::<T>().unwrap();
+    }
+}
+
+/// Transactional variable, only modified through `Stm` actions.
+pub struct TVar<T> {
+    id: usize,
+    cell: Arc<Cell<T>>,
+}
+
+impl<T> Clone for TVar<T> {
+    fn clone(&self) -> Self {
+        TVar {
+            id: self.id,
+            cell: self.cell.clone(),
+        }
+    }
+}
+
+impl<T> TVar<T> {
+    pub fn new(value: T) -> Self {
+        TVar {
+            id: NEXT_TVAR_ID.fetch_add(1, Ordering::SeqCst),
+            cell: Arc::new(Cell {
+                state: Mutex::new((0, Arc::new(value))),
+            }),
+        }
+    }
+}
+
+// Stm
+
+/// A transaction which can be composed, and run with `atomically`.
+///
+/// Like `Io` every action is a value; nothing happens until it is run.
+pub trait Stm: Sized {
+    type Output;
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output>;
+
+    fn map<B, F>(self, f: F) -> StmMap<Self, F>
+    where
+        F: FnOnce(Self::Output) -> B,
+    {
+        StmMap { stm: self, f }
+    }
+
+    fn pure<T>(t: T) -> StmPure<T> {
+        StmPure { stm: t }
+    }
+
+    fn and_then<B, F>(self, f: F) -> StmBind<Self, F>
+    where
+        F: FnOnce(Self::Output) -> B + Clone,
+    {
+        StmBind { stm: self, f }
+    }
+
+    fn then<Mb>(self, mb: Mb) -> StmBind<Self, impl FnOnce(Self::Output) -> Mb + Clone>
+    where
+        Mb: Stm + Clone,
+    {
+        StmBind {
+            stm: self,
+            f: |_| mb,
+        }
+    }
+
+    /// Run `other` instead if this transaction retries.
+    fn or_else<B>(self, other: B) -> StmOrElse<Self, B>
+    where
+        B: Stm<Output = Self::Output>,
+    {
+        StmOrElse {
+            lhs: self,
+            rhs: other,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StmMap<S, F> {
+    stm: S,
+    f: F,
+}
+
+impl<A, F, B> Stm for StmMap<A, F>
+where
+    A: Stm,
+    F: FnOnce(A::Output) -> B,
+{
+    type Output = B;
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output> {
+        self.stm.run_stm(tx).map(self.f)
+    }
+}
+
+#[derive(Clone)]
+pub struct StmPure<A> {
+    stm: A,
+}
+
+impl<A> Stm for StmPure<A> {
+    type Output = A;
+
+    fn run_stm(self, _tx: &mut Transaction) -> StmResult<Self::Output> {
+        Ok(self.stm)
+    }
+}
+
+#[derive(Clone)]
+pub struct StmBind<A, F> {
+    stm: A,
+    f: F,
+}
+
+impl<A, B, F> Stm for StmBind<A, F>
+where
+    A: Stm,
+    B: Stm,
+    F: FnOnce(A::Output) -> B,
+{
+    type Output = B::Output;
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output> {
+        (self.f)(self.stm.run_stm(tx)?).run_stm(tx)
+    }
+}
+
+#[derive(Clone)]
+pub struct StmOrElse<A, B> {
+    lhs: A,
+    rhs: B,
+}
+
+impl<A, B> Stm for StmOrElse<A, B>
+where
+    A: Stm,
+    B: Stm<Output = A::Output>,
+{
+    type Output = A::Output;
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output> {
+        let writes = tx.writes.clone();
+
+        match self.lhs.run_stm(tx) {
+            Err(Abort::Retry) => {
+                // Keep the reads so a retry of `rhs` also waits on them
+                tx.writes = writes;
+                self.rhs.run_stm(tx)
+            }
+            result => result,
+        }
+    }
+}
+
+pub fn or_else<A, B>(lhs: A, rhs: B) -> StmOrElse<A, B>
+where
+    A: Stm,
+    B: Stm<Output = A::Output>,
+{
+    StmOrElse { lhs, rhs }
+}
+
+// Actions
+
+#[derive(Clone)]
+pub struct NewTVarStm<T> {
+    value: T,
+}
+
+impl<T> Stm for NewTVarStm<T> {
+    type Output = TVar<T>;
+
+    fn run_stm(self, _tx: &mut Transaction) -> StmResult<Self::Output> {
+        Ok(TVar::new(self.value))
+    }
+}
+
+pub fn new_tvar<T>(value: T) -> NewTVarStm<T> {
+    NewTVarStm { value }
+}
+
+#[derive(Clone)]
+pub struct ReadTVarStm<T> {
+    tvar: TVar<T>,
+}
+
+impl<T> Stm for ReadTVarStm<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Output = T;
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output> {
+        tx.read(&self.tvar).map(|value| (*value).clone())
+    }
+}
+
+pub fn read_tvar<T>(tvar: TVar<T>) -> ReadTVarStm<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    ReadTVarStm { tvar }
+}
+
+#[derive(Clone)]
+pub struct WriteTVarStm<T> {
+    tvar: TVar<T>,
+    value: T,
+}
+
+impl<T> Stm for WriteTVarStm<T>
+where
+    T: Send + Sync + 'static,
+{
+    type Output = ();
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output> {
+        tx.write(&self.tvar, self.value);
+        Ok(())
+    }
+}
+
+pub fn write_tvar<T>(tvar: TVar<T>, value: T) -> WriteTVarStm<T>
+where
+    T: Send + Sync + 'static,
+{
+    WriteTVarStm { tvar, value }
+}
+
+#[derive(Clone)]
+pub struct ModifyTVarStm<T, F> {
+    tvar: TVar<T>,
+    f: F,
+}
+
+impl<T, F> Stm for ModifyTVarStm<T, F>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce(T) -> T,
+{
+    type Output = ();
+
+    fn run_stm(self, tx: &mut Transaction) -> StmResult<Self::Output> {
+        let value = (*tx.read(&self.tvar)?).clone();
+        tx.write(&self.tvar, (self.f)(value));
+        Ok(())
+    }
+}
+
+pub fn modify_tvar<T, F>(tvar: TVar<T>, f: F) -> ModifyTVarStm<T, F>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce(T) -> T,
+{
+    ModifyTVarStm { tvar, f }
+}
+
+pub struct RetryStm<T> {
+    _t: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for RetryStm<T> {
+    fn clone(&self) -> Self {
+        retry()
+    }
+}
+
+impl<T> Stm for RetryStm<T> {
+    type Output = T;
+
+    fn run_stm(self, _tx: &mut Transaction) -> StmResult<Self::Output> {
+        Err(Abort::Retry)
+    }
+}
+
+/// Abandon the transaction and block until one of the `TVar`s it read changes.
+pub fn retry<T>() -> RetryStm<T> {
+    RetryStm {
+        _t: std::marker::PhantomData,
+    }
+}
+
+#[derive(Clone)]
+pub struct CheckStm {
+    condition: bool,
+}
+
+impl Stm for CheckStm {
+    type Output = ();
+
+    fn run_stm(self, _tx: &mut Transaction) -> StmResult<Self::Output> {
+        if self.condition {
+            Ok(())
+        } else {
+            Err(Abort::Retry)
+        }
+    }
+}
+
+/// `retry` unless `condition` holds.
+pub fn check(condition: bool) -> CheckStm {
+    CheckStm { condition }
+}
+
+// Running transactions
+
+#[derive(Clone)]
+pub struct AtomicallyIo<S> {
+    stm: S,
+}
+
+impl<S> Io for AtomicallyIo<S>
+where
+    S: Stm + Clone,
+{
+    type Output = S::Output;
+
+    fn run(self) -> Self::Output {
+        loop {
+            let started = *COMMITS.lock().unwrap();
+            let mut tx = Transaction::new();
+
+            match self.stm.clone().run_stm(&mut tx) {
+                Ok(output) => {
+                    if tx.commit() {
+                        return output;
+                    }
+                }
+                Err(Abort::Conflict) => {}
+                Err(Abort::Retry) => {
+                    let commits = COMMITS.lock().unwrap();
+                    drop(COMMITTED.wait_while(commits, |commits| *commits == started));
+                }
+            }
+        }
+    }
+}
+
+/// Run a transaction as a single atomic `Io` step, re-running it on conflict.
+pub fn atomically<S>(stm: S) -> AtomicallyIo<S>
+where
+    S: Stm + Clone,
+{
+    AtomicallyIo { stm }
+}
+
+#[derive(Clone)]
+pub struct NewTVarIo<T> {
+    value: T,
+}
+
+impl<T> Io for NewTVarIo<T> {
+    type Output = TVar<T>;
+
+    fn run(self) -> Self::Output {
+        TVar::new(self.value)
+    }
+}
+
+pub fn new_tvar_io<T>(value: T) -> NewTVarIo<T> {
+    NewTVarIo { value }
+}
+
+#[derive(Clone)]
+pub struct ReadTVarIo<T> {
+    tvar: TVar<T>,
+}
+
+impl<T> Io for ReadTVarIo<T>
+where
+    T: Clone,
+{
+    type Output = T;
+
+    fn run(self) -> Self::Output {
+        (*self.tvar.cell.state.lock().unwrap().1).clone()
+    }
+}
+
+/// Read the current value outside of any transaction.
+pub fn read_tvar_io<T>(tvar: TVar<T>) -> ReadTVarIo<T>
+where
+    T: Clone,
+{
+    ReadTVarIo { tvar }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control::concurrent::{fork_io, wait_thread};
+
+    use super::*;
+
+    #[test]
+    fn test_atomically_read_write() {
+        let tvar = TVar::new(1);
+        let io = atomically(
+            read_tvar(tvar.clone()).and_then(|x| write_tvar(tvar.clone(), x + 1).map(move |_| x)),
+        );
+
+        assert_eq!(io.run(), 1);
+        assert_eq!(read_tvar_io(tvar).run(), 2);
+    }
+
+    #[test]
+    fn test_or_else_discards_writes() {
+        let tvar = TVar::new(0);
+        let lhs = write_tvar(tvar.clone(), 1).then(retry::<&str>());
+        let rhs = StmPure::<()>::pure("rhs");
+
+        assert_eq!(atomically(lhs.or_else(rhs)).run(), "rhs");
+        assert_eq!(read_tvar_io(tvar).run(), 0);
+    }
+
+    #[test]
+    fn test_retry_blocks_until_write() {
+        let tvar = TVar::new(0);
+        let reader = fork_io(atomically(
+            read_tvar(tvar.clone()).and_then(|x| check(x > 0).map(move |_| x)),
+        ))
+        .run();
+
+        atomically(write_tvar(tvar.clone(), 5)).run();
+
+        assert_eq!(wait_thread(reader).run().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_concurrent_increments() {
+        let tvar = TVar::new(0);
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let tvar = tvar.clone();
+                fork_io(atomically(modify_tvar(tvar, |x| x + 1))).run()
+            })
+            .collect();
+
+        for handle in handles {
+            wait_thread(handle).run().unwrap();
+        }
+
+        assert_eq!(read_tvar_io(tvar).run(), 8);
+    }
+
+    #[test]
+    fn test_reads_see_a_consistent_snapshot() {
+        // Invariant: x + y == 0
+        let x = TVar::new(0);
+        let y = TVar::new(0);
+        let attempts = Arc::new(AtomicUsize::new(0));
+
+        let stm = read_tvar(x.clone()).and_then({
+            let (x, y, attempts) = (x.clone(), y.clone(), attempts.clone());
+            move |a| {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // Another transaction commits between the two reads of the first attempt
+                    let (x, y) = (x.clone(), y.clone());
+                    std::thread::spawn(move || {
+                        atomically(write_tvar(x, 1).then(write_tvar(y, -1))).run()
+                    })
+                    .join()
+                    .unwrap();
+                }
+                read_tvar(y).map(move |b| {
+                    assert_eq!(a + b, 0, "ran on an inconsistent snapshot");
+                    (a, b)
+                })
+            }
+        });
+
+        assert_eq!(atomically(stm).run(), (1, -1));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
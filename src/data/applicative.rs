@@ -1,343 +1,113 @@
-// // use super::functor::Functor;
-
-// ! Not useful until the HKT family is implemented
-
-// // pub trait Applicative: Functor {
-// //     fn pure<A>(a: A) -> Self::Of<A>;
-
-// //     fn apply<B, F>(self, f: Self::Of<F>) -> Self::Of<B>
-// //     where
-// //         F: Fn(Self::HktArg1) -> B;
-// // }
-
-// // #[cfg(test)]
-// // mod tests {
-
-// //     use super::*;
-// //     use crate::base::hkt::Hkt1;
-// //     use crate::impl_hkt1;
-
-// //     impl<T> Applicative for Option<T> {
-// //         fn pure<A>(a: A) -> Self::Of<A> {
-// //             Some(a)
-// //         }
-
-// //         fn apply<B, F>(self, f: Self::Of<F>) -> Self::Of<B>
-// //         where
-// //             F: Fn(Self::HktArg1) -> B,
-// //         {
-// //             match (self, f) {
-// //                 (Some(x), Some(f)) => Some(f(x)),
-// //                 _ => None,
-// //             }
-// //         }
-// //     }
-
-// //     #[test]
-// //     fn test_applicative_pure() {
-// //         assert_eq!(Option::<i32>::pure(1), Some(1));
-// //     }
-
-// //     #[test]
-// //     fn test_applicative_apply() {
-// //         assert_eq!(None::<i32>.apply(None::<Fn(i32) -> i32>), None);
-// //         assert_eq!(None.apply(Some(|x: i32| x + 1)), None);
-
-// //         assert_eq!(Some(1).apply(None::<Fn(i32) -> i32>), None);
-// //         assert_eq!(Some(1).apply(Some(|x: i32| x + 1)), Some(2));
-// //     }
-// // }
-
-// use crate::base::hkt::Hkt1;
-
-// use super::functor::Functor;
-
-// // pub trait Applicative: Functor<A> {
-// //     type Arg1;
-
-// //     type Pure<T>: Applicative<T>;
-// //     type Af<B, F>: Applicative<F>
-// //     where
-// //         F: Fn(A) -> B + Clone;
-// //     type Ap<B, F>: Applicative<B>
-// //     where
-// //         F: Fn(A) -> B + Clone;
-
-// //     fn pure(a: A) -> Self::Pure<A>;
-
-// //     fn apply<B, F>(self, af: Self::Af<B, F>) -> Self::Ap<B, F>
-// //     where
-// //         F: Fn(A) -> B + Clone;
-// // }
-
-// pub trait Applicative: Functor {
-//     type Pure<T>: Applicative + Hkt1<HktArg1 = T>;
-//     type Af<B, F>: Applicative + Hkt1<HktArg1 = F>
-//     where
-//         F: Fn(Self::HktArg1) -> B + Clone;
-
-//     type Ap<B, F>: Applicative + Hkt1<HktArg1 = B>
-//     where
-//         F: Fn(Self::HktArg1) -> B + Clone;
-
-//     fn pure(x: Self::HktArg1) -> Self::Pure<Self::HktArg1>;
-
-//     fn apply<B, F>(self, af: Self::Af<B, F>) -> Self::Ap<B, F>
-//     where
-//         F: Fn(Self::HktArg1) -> B + Clone;
-// }
-
-// #[cfg(test)]
-// mod tests {
-
-//     use crate::base::hkt::HktIter;
-
-//     use super::*;
-
-//     // impl<A> Applicative<A> for Option<A> {
-//     //     type Pure<T> = Option<T>;
-//     //     // type Af<B, F> = Option<F>
-//     //     // where
-//     //     //     F: Fn(A) -> B + Clone;
-//     //     type Ap<B, F> = Option<B>
-//     //     where
-//     //         F: Fn(A) -> B + Clone;
-
-//     //     fn pure(a: A) -> Self::Pure<A> {
-//     //         Some(a)
-//     //     }
-
-//     //     // fn apply<B, F>(self, f: Self::Af<B, F>) -> Self::Ap<B, F>
-//     //     fn apply<B, F, Af>(self, f: Af) -> Self::Ap<B, F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone,
-//     //         Af: Applicative<F> + HktOption<F>,
-//     //     {
-//     //         match (self, f) {
-//     //             (Some(x), Some(f)) => Some(f(x)),
-//     //             _ => None,
-//     //         }
-//     //     }
-//     // }
-
-//     // impl<A> Applicative<A> for Option<A> {
-//     //     type Pure<T> = Option<T>;
-//     //     type Af<B, F> = Option<F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone;
-//     //     type Ap<B, F> = Option<B>
-//     //     where
-//     //         F: Fn(A) -> B + Clone;
-
-//     //     fn pure(a: A) -> Self::Pure<A> {
-//     //         Some(a)
-//     //     }
-
-//     //     fn apply<B, F>(self, f: Self::Af<B, F>) -> Self::Ap<B, F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone,
-//     //     {
-//     //         match (self, f) {
-//     //             (Some(x), Some(f)) => Some(f(x)),
-//     //             _ => None,
-//     //         }
-//     //     }
-//     // }
-
-//     // Hkt Applicative for Option
-
-//     impl<A> Applicative for Option<A> {
-//         type Pure<T> = Option<T>;
-//         type Af<B, F> = Option<F>
-//         where
-//             F: Fn(A) -> B + Clone;
-//         type Ap<B, F> = Option<B>
-//         where
-//             F: Fn(A) -> B + Clone;
-
-//         fn pure(a: A) -> Self::Pure<A> {
-//             Some(a)
-//         }
-
-//         fn apply<B, F>(self, f: Self::Af<B, F>) -> Self::Ap<B, F>
-//         where
-//             F: Fn(A) -> B + Clone,
-//         {
-//             match (self, f) {
-//                 (Some(x), Some(f)) => Some(f(x)),
-//                 _ => None,
-//             }
-//         }
-//     }
-
-//     // ! No blancket impl
-//     // impl<A, I> Applicative<A> for I
-//     // where
-//     //     I: HktIter + Iterator<Item = A>,
-//     // {
-//     //     type Pure<T> = std::iter::Once<T>;
-//     //     // type Af<B, F> = std::iter::Map<I, F>
-//     //     // where
-//     //     //     F: Fn(A) -> B + Clone;
-//     //     type Ap<B, F> =  std::iter::FlatMap<Self, std::iter::Map<std::vec::IntoIter<F>, F>, F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone;
-
-//     //     fn pure(a: A) -> Self::Pure<A> {
-//     //         std::iter::once(a)
-//     //     }
-
-//     //     // fn apply<B, F>(self, f: Self::Af<B, F>) -> Self::Ap<B, F>
-//     //     fn apply<B, F, Af>(self, f: std::iter::Map<std::iter::Once<F>, F>) -> Self::Ap<B, F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone,
-//     //     {
-//     //         // Cartesian product
-//     //         self.flat_map(move |x| f.clone().map(move |f| f(x)))
-//     //         // A -> List<B>
-//     //     }
-//     // }
-
-//     // Impl for std::vec::IntoIter<A>
-//     // Map<std::vec::IntoIter<F>
-
-//     // impl<A> Applicative<A> for std::vec::IntoIter<A> {
-//     //     type Pure<T> = std::vec::IntoIter<T>;
-//     //     type Af<B, F> = impl Applicative<F> + Iterator<Item = F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone;
-//     //     // FlatMap<std::vec::IntoIter<A>, Map<std::vec::IntoIter<F>, {closure@src/data/applicative.rs:120:50: 120:58}>, {closure@src/data/applicative.rs:120:27: 120:35}>
-//     //     type Ap<B, F> = std::iter::FlatMap<Self, std::iter::Map<std::vec::IntoIter<F>, F>, F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone;
-
-//     //     fn pure(a: A) -> Self::Pure<A> {
-//     //         vec![a].into_iter()
-//     //     }
-
-//     //     fn apply<B, F>(self, f: Self::Af<B, F>) -> Self::Ap<B, F>
-//     //     where
-//     //         F: Fn(A) -> B + Clone,
-//     //     {
-//     //         // Cartesian product
-//     //         self.flat_map(move |x| f.clone().map(move |f| f(x)))
-//     //         // A -> List<B>
-//     //     }
-//     // }
-
-//     impl<A> Applicative for std::vec::IntoIter<A> {
-//         type Pure<T> = std::iter::Once<T>;
-//         type Af<B, F> = impl Applicative + Hkt1<HktArg1 = F> + Iterator<Item = F> + Clone
-//         where
-//             F: Fn(A) -> B + Clone;
-
-//         type Ap<B, F> = std::iter::FlatMap<Self, std::iter::Map<Self::Af<B,F>, impl FnMut(F) -> B>, impl FnMut(A) -> std::iter::Map<Self::Af<B,F>, impl FnMut(F) -> B>>
-//         where
-//             F: Fn(A) -> B + Clone;
-
-//         fn pure(x: Self::HktArg1) -> Self::Pure<Self::HktArg1> {
-//             std::iter::once(x)
-//         }
-
-//         fn apply<B, F>(self, af: Self::Af<B, F>) -> Self::Ap<B, F>
-//         where
-//             F: Fn(A) -> B + Clone,
-//         {
-//             let g = |x: A| af.clone().map(move |f| f(x));
-//             self.flat_map(g)
-//         }
-//     }
-
-//     impl<A> Applicative for std::iter::Once<A> {
-//         type Pure<T> = std::iter::Once<T>;
-//         type Af<B, F> = impl Applicative + Hkt1<HktArg1 = F> + Iterator<Item = F> + Clone
-//         where
-//             F: Fn(A) -> B + Clone;
-
-//         type Ap<B, F> = std::iter::FlatMap<Self, std::iter::Map<Self::Af<B,F>, impl FnMut(F) -> B>, impl FnMut(A) -> std::iter::Map<Self::Af<B,F>, impl FnMut(F) -> B>>
-//         where
-//             F: Fn(A) -> B + Clone;
-
-//         fn pure(x: Self::HktArg1) -> Self::Pure<Self::HktArg1> {
-//             std::iter::once(x)
-//         }
-
-//         fn apply<B, F>(self, af: Self::Af<B, F>) -> Self::Ap<B, F>
-//         where
-//             F: Fn(A) -> B + Clone,
-//         {
-//             let g = |x: A| af.clone().map(move |f| f(x));
-//             self.flat_map(g)
-//         }
-//     }
-
-//     impl<I, A, B, F> Applicative for std::iter::Map<I, F>
-//     where
-//         I: Iterator<Item = A>,
-//         F: Fn(A) -> B + Clone,
-//     {
-//         type Pure<T> = std::iter::Once<T>;
-//         type Af<C, G> = impl Applicative + Hkt1<HktArg1 = G> + Iterator<Item = G> + Clone
-//         where
-//             G: Fn(Self::HktArg1) -> C + Clone;
-
-//         type Ap<C, G> = std::iter::FlatMap<Self,
-//         std::iter::Map<Self::Af<C,G>, impl Fn(G) -> C + Clone>, impl Fn(Self::HktArg1) -> std::iter::Map<Self::Af<C,G>, impl Fn(G) -> C + Clone> + Clone>
-//         where
-//             G: Fn(Self::HktArg1) -> C + Clone;
-
-//         fn pure(x: Self::HktArg1) -> Self::Pure<Self::HktArg1> {
-//             std::iter::once(x)
-//         }
-
-//         fn apply<C, G>(self, af: Self::Af<C, G>) -> Self::Ap<C, G>
-//         where
-//             G: Fn(Self::HktArg1) -> C + Clone,
-//         {
-//             let g = |x: Self::HktArg1| af.clone().map(move |f| f(x));
-//             self.flat_map(g)
-//         }
-//     }
-
-//     impl<A, B, I, U, F> Applicative for std::iter::FlatMap<I, U, F>
-//     where
-//         I: Iterator<Item = A>,
-//         U: Iterator<Item = B>,
-//         F: Fn(A) -> U + Clone,
-//     {
-//         type Pure<T> = std::iter::Once<T>;
-//         type Af<C, G> = impl Applicative + Hkt1<HktArg1 = G> + Iterator<Item = G> + Clone
-//         where
-//             G: Fn(B) -> C + Clone;
-
-//         type Ap<C, G> = std::iter::FlatMap<Self, std::iter::Map<Self::Af<C,G>, impl FnMut(G) -> C>, impl FnMut(B) -> std::iter::Map<Self::Af<C,G>, impl FnMut(G) -> C>>
-//         where
-//             G: Fn(B) -> C + Clone;
-
-//         fn pure(x: Self::HktArg1) -> Self::Pure<Self::HktArg1> {
-//             std::iter::once(x)
-//         }
-
-//         fn apply<C, G>(self, af: Self::Af<C, G>) -> Self::Ap<C, G>
-//         // where
-//             G: Fn(B) -> C + Clone,
-//         {
-//             let g = |x: B| af.clone().map(move |f| f(x));
-//             self.flat_map(g)
-//         }
-//     }
-
-//     #[test]
-//     fn test_applicative_pure() {
-//         assert_eq!(Option::<i32>::pure(1), Some(1));
-//     }
-
-//     #[test]
-//     fn test_applicative_apply() {
-//         assert_eq!(None::<i32>.apply(None::<fn(i32) -> i32>), None);
-//         assert_eq!(None.apply(Some(|x: i32| x + 1)), None);
-
-//         assert_eq!(Some(1).apply(None::<fn(i32) -> i32>), None);
-//         assert_eq!(Some(1).apply(Some(|x: i32| x + 1)), Some(2));
-//     }
-// }
+use crate::base::hkt::Hkt1;
+
+use super::functor::Functor;
+
+pub trait Applicative: Functor {
+    /// The same type constructor applied to another argument
+    type Of<T>: Hkt1<HktArg1 = T>;
+
+    fn pure(a: Self::HktArg1) -> Self;
+
+    /// Lift a unary function to actions, staying within `Of`
+    ///
+    /// f a -> (a -> b) -> f b
+    fn lift_a<B, F>(self, f: F) -> Self::Of<B>
+    where
+        F: Fn(Self::HktArg1) -> B + Clone;
+
+    /// Sequential application
+    ///
+    /// f a -> f (a -> b) -> f b
+    fn apply<B, F>(self, ff: Self::Of<F>) -> Self::Of<B>
+    where
+        F: Fn(Self::HktArg1) -> B + Clone;
+
+    /// Lift a binary function to actions
+    ///
+    /// f a -> f b -> (a -> b -> c) -> f c
+    fn lift_a2<B, C, F>(self, fb: Self::Of<B>, f: F) -> Self::Of<C>
+    where
+        B: Clone,
+        F: Fn(Self::HktArg1, B) -> C + Clone;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use super::*;
+    use crate::data::tree::Tree;
+
+    // Applicative laws
+
+    fn identity<Fa>(v: Fa)
+    where
+        Fa: Applicative + Clone + PartialEq<Fa::Of<Fa::HktArg1>> + Debug,
+        Fa::Of<Fa::HktArg1>: Debug,
+        Fa::Of<fn(Fa::HktArg1) -> Fa::HktArg1>:
+            Applicative<HktArg1 = fn(Fa::HktArg1) -> Fa::HktArg1>,
+    {
+        let id: fn(Fa::HktArg1) -> Fa::HktArg1 = |x| x;
+        let pure_id = <Fa::Of<fn(Fa::HktArg1) -> Fa::HktArg1>>::pure(id);
+        assert_eq!(v.clone(), v.apply(pure_id));
+    }
+
+    #[test]
+    fn test_identity_law() {
+        identity(None::<i32>);
+        identity(Some(1));
+        identity(Ok::<i32, String>(1));
+        identity(Err::<i32, String>("e".to_string()));
+        identity(Vec::<i32>::new());
+        identity(vec![1, 2, 3]);
+        identity(Tree {
+            value: 1,
+            children: vec![Tree {
+                value: 2,
+                children: vec![],
+            }],
+        });
+    }
+
+    #[test]
+    fn test_homomorphism_law() {
+        let f = |x: i32| x * 2;
+
+        assert_eq!(Option::pure(3).apply(Option::pure(f)), Option::pure(f(3)));
+        assert_eq!(
+            Result::<i32, ()>::pure(3).apply(Result::pure(f)),
+            Result::pure(f(3))
+        );
+        assert_eq!(Vec::pure(3).apply(Vec::pure(f)), Vec::pure(f(3)));
+        assert_eq!(Tree::pure(3).apply(Tree::pure(f)), Tree::pure(f(3)));
+    }
+
+    #[test]
+    fn test_interchange_law() {
+        // u <*> pure y = pure ($ y) <*> u
+        let y = 3;
+        let fs: Vec<fn(i32) -> i32> = vec![|x| x + 1, |x| x * 2];
+
+        assert_eq!(
+            Vec::pure(y).apply(fs.clone()),
+            fs.apply(Vec::pure(move |f: fn(i32) -> i32| f(y)))
+        );
+    }
+
+    #[test]
+    fn test_lift_a() {
+        assert_eq!(Some(1).lift_a(|a| a + 1), Some(2));
+        assert_eq!(vec![1, 2].lift_a(|a| a + 1), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_lift_a2() {
+        assert_eq!(Some(1).lift_a2(Some(2), |a, b| a + b), Some(3));
+        assert_eq!(Some(1).lift_a2(None::<i32>, |a, b| a + b), None);
+
+        assert_eq!(
+            vec![1, 2].lift_a2(vec![10, 20], |a, b| a + b),
+            vec![11, 21, 12, 22]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::base::hkt::Hkt1;
+
+use super::functor::Functor;
+
+/// A value of one of two types, as Haskell's `Data.Either`.
+///
+/// Unlike `Result` neither side means failure; `Functor` maps over `Right` by convention.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+impl<A, B> Either<A, B> {
+    pub fn either<C, F, G>(self, f: F, g: G) -> C
+    where
+        F: FnOnce(A) -> C,
+        G: FnOnce(B) -> C,
+    {
+        match self {
+            Either::Left(a) => f(a),
+            Either::Right(b) => g(b),
+        }
+    }
+
+    pub fn is_left(&self) -> bool {
+        matches!(self, Either::Left(_))
+    }
+
+    pub fn is_right(&self) -> bool {
+        matches!(self, Either::Right(_))
+    }
+
+    pub fn left(self) -> Option<A> {
+        match self {
+            Either::Left(a) => Some(a),
+            Either::Right(_) => None,
+        }
+    }
+
+    pub fn right(self) -> Option<B> {
+        match self {
+            Either::Left(_) => None,
+            Either::Right(b) => Some(b),
+        }
+    }
+}
+
+impl<L, R> Hkt1 for Either<L, R> {
+    type HktArg1 = R;
+}
+
+impl<L, A> Functor for Either<L, A> {
+    type Map<B, F> = Either<L, B>
+    where
+        F: Fn(A) -> B + Clone;
+
+    #[inline(always)]
+    fn fmap<B, F>(self, f: F) -> Either<L, B>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        match self {
+            Either::Left(l) => Either::Left(l),
+            Either::Right(a) => Either::Right(f(a)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_either() {
+        let l: Either<i32, &str> = Either::Left(1);
+        let r: Either<i32, &str> = Either::Right("a");
+
+        assert_eq!(l.either(|x| x + 1, |s| s.len() as i32), 2);
+        assert_eq!(r.either(|x| x + 1, |s| s.len() as i32), 1);
+        assert!(l.is_left() && r.is_right());
+        assert_eq!(l.left(), Some(1));
+        assert_eq!(r.left(), None);
+    }
+
+    #[test]
+    fn test_either_fmap() {
+        let l: Either<i32, i32> = Either::Left(1);
+        let r: Either<i32, i32> = Either::Right(1);
+
+        assert_eq!(l.fmap(|x| x * 10), Either::Left(1));
+        assert_eq!(r.fmap(|x| x * 10), Either::Right(10));
+    }
+}
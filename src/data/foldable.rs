@@ -0,0 +1,189 @@
+use crate::base::hkt::Hkt1;
+use crate::prelude::Io;
+
+use super::monoid::Monoid;
+
+pub trait Foldable: Hkt1 + Sized {
+    /// Right-associative fold of a structure
+    ///
+    /// t a -> (a -> b -> b) -> b -> b
+    fn foldr<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(Self::HktArg1, B) -> B;
+
+    /// Left-associative fold of a structure
+    ///
+    /// t a -> (b -> a -> b) -> b -> b
+    fn foldl<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(B, Self::HktArg1) -> B;
+
+    /// Map each element to a monoid and combine the results
+    ///
+    /// Monoid m => t a -> (a -> m) -> m
+    fn fold_map<M, F>(self, f: F) -> M::A
+    where
+        M: Monoid,
+        F: Fn(Self::HktArg1) -> M::A,
+    {
+        self.foldr(|a, acc| M::mappend(f(a), acc), M::mempty())
+    }
+
+    fn to_list(self) -> Vec<Self::HktArg1> {
+        self.foldl(
+            |mut acc, a| {
+                acc.push(a);
+                acc
+            },
+            Vec::new(),
+        )
+    }
+
+    fn length(self) -> usize {
+        self.foldl(|acc, _| acc + 1, 0)
+    }
+
+    fn null(self) -> bool {
+        self.length() == 0
+    }
+
+    fn elem(self, x: &Self::HktArg1) -> bool
+    where
+        Self::HktArg1: PartialEq,
+    {
+        self.foldl(|acc, a| acc || a == *x, false)
+    }
+
+    fn maximum(self) -> Option<Self::HktArg1>
+    where
+        Self::HktArg1: Ord,
+    {
+        self.foldl(|acc, a| acc.max(Some(a)), None)
+    }
+
+    fn minimum(self) -> Option<Self::HktArg1>
+    where
+        Self::HktArg1: Ord,
+    {
+        self.foldl(
+            |acc, a| match acc {
+                Some(b) => Some(b.min(a)),
+                None => Some(a),
+            },
+            None,
+        )
+    }
+
+    /// Run an action for each element, discarding the results
+    ///
+    /// mapM_ :: t a -> (a -> IO b) -> IO ()
+    fn for_each_io<I, F>(self, f: F) -> ForEachIo<Self, F>
+    where
+        I: Io,
+        F: Fn(Self::HktArg1) -> I,
+    {
+        ForEachIo { xs: self, f }
+    }
+}
+
+#[derive(Clone)]
+pub struct ForEachIo<T, F> {
+    xs: T,
+    f: F,
+}
+
+impl<T, I, F> Io for ForEachIo<T, F>
+where
+    T: Foldable,
+    I: Io,
+    F: Fn(T::HktArg1) -> I,
+{
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        let f = self.f;
+        self.xs.foldl(
+            |(), a| {
+                f(a).run();
+            },
+            (),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{applicative::Applicative, monoid::Sum, tree::Tree};
+
+    #[test]
+    fn test_foldr_foldl_order() {
+        let xs = vec!["a", "b", "c"];
+
+        assert_eq!(
+            xs.clone()
+                .foldr(|a, acc| format!("({}{})", a, acc), String::new()),
+            "(a(b(c)))"
+        );
+        assert_eq!(
+            xs.foldl(|acc, a| format!("({}{})", acc, a), String::new()),
+            "(((a)b)c)"
+        );
+    }
+
+    #[test]
+    fn test_fold_map() {
+        assert_eq!(None::<i32>.fold_map::<Sum<i32>, _>(Sum), Sum(0));
+        assert_eq!(Some(3).fold_map::<Sum<i32>, _>(Sum), Sum(3));
+        assert_eq!(
+            vec![1, 2, 3].fold_map::<Sum<i32>, _>(|x| Sum(x * 2)),
+            Sum(12)
+        );
+
+        let tree = Tree {
+            value: 1,
+            children: vec![Tree::pure(2), Tree::pure(3)],
+        };
+        assert_eq!(tree.fold_map::<Sum<i32>, _>(Sum), Sum(6));
+    }
+
+    #[test]
+    fn test_derived_folds() {
+        let tree = Tree {
+            value: 2,
+            children: vec![Tree::pure(5), Tree::pure(1)],
+        };
+
+        assert_eq!(tree.clone().to_list(), vec![2, 5, 1]);
+        assert_eq!(tree.clone().length(), 3);
+        assert!(!tree.clone().null());
+        assert!(tree.clone().elem(&5));
+        assert_eq!(tree.clone().maximum(), Some(5));
+        assert_eq!(tree.minimum(), Some(1));
+
+        assert!(None::<i32>.null());
+        assert_eq!(Vec::<i32>::new().maximum(), None);
+    }
+
+    struct Record<'a>(&'a std::cell::RefCell<Vec<i32>>, i32);
+
+    impl Io for Record<'_> {
+        type Output = i32;
+
+        fn run(self) -> i32 {
+            self.0.borrow_mut().push(self.1);
+            self.1
+        }
+    }
+
+    #[test]
+    fn test_for_each_io() {
+        let seen = std::cell::RefCell::new(Vec::new());
+
+        let io = vec![1, 2, 3].for_each_io(|x| Record(&seen, x));
+        assert!(seen.borrow().is_empty());
+
+        io.run();
+        assert_eq!(*seen.borrow(), vec![1, 2, 3]);
+    }
+}
@@ -1,180 +1,171 @@
-// use crate::base::hkt::Hkt1;
-
-// use super::functor::Functor;
-
-// ! Not useful until the HKT family is implemented
-
-// impl<A> Hkt1 for std::vec::IntoIter<A> {
-//     type HktArg1 = A;
-// }
-
-// impl<A> Functor for std::vec::IntoIter<A> {
-//     type Map<B, F> = std::iter::Map<Self, F>
-//     where
-//         F: Fn(A) -> B + Clone;
-
-//     #[inline(always)]
-//     fn fmap<B, F>(self, f: F) -> std::iter::Map<Self, F>
-//     where
-//         F: Fn(A) -> B + Clone,
-//     {
-//         self.map(f)
-//     }
-
-//     #[inline(always)]
-//     fn fmap1<F>(self, f: F) -> std::iter::Map<Self, F>
-//     where
-//         F: Fn(A) -> A + Clone,
-//     {
-//         self.map(f)
-//     }
-// }
-
-// impl<A> Hkt1 for std::iter::Once<A> {
-//     type HktArg1 = A;
-// }
-
-// impl<A> Functor for std::iter::Once<A> {
-//     type Map<B, F> = std::iter::Map<Self, F>
-//     where
-//         F: Fn(A) -> B + Clone;
-
-//     #[inline(always)]
-//     fn fmap<B, F>(self, f: F) -> std::iter::Map<Self, F>
-//     where
-//         F: Fn(A) -> B + Clone,
-//     {
-//         self.map(f)
-//     }
-
-//     #[inline(always)]
-//     fn fmap1<F>(self, f: F) -> std::iter::Map<Self, F>
-//     where
-//         F: Fn(A) -> A + Clone,
-//     {
-//         self.map(f)
-//     }
-// }
-
-// impl<I, A, B, F> Hkt1 for std::iter::Map<I, F>
-// where
-//     I: Iterator<Item = A>,
-//     F: Fn(A) -> B + Clone,
-// {
-//     type HktArg1 = B;
-// }
-
-// impl<I, A, B, F> Functor for std::iter::Map<I, F>
-// where
-//     I: Iterator<Item = A>,
-//     F: Fn(A) -> B + Clone,
-// {
-//     type Map<C, G> = std::iter::Map<Self, G>
-//     where
-//         G: Fn(Self::HktArg1) -> C + Clone;
-
-//     #[inline(always)]
-//     fn fmap<C, G>(self, g: G) -> std::iter::Map<Self, G>
-//     where
-//         G: Fn(Self::HktArg1) -> C + Clone,
-//     {
-//         self.map(g)
-//     }
-
-//     #[inline(always)]
-//     fn fmap1<G>(self, g: G) -> std::iter::Map<Self, G>
-//     where
-//         G: Fn(Self::HktArg1) -> Self::HktArg1 + Clone,
-//     {
-//         self.map(g)
-//     }
-// }
-
-// impl<A, B, I, U, F> Hkt1 for std::iter::FlatMap<I, U, F>
-// where
-//     I: Iterator<Item = A>,
-//     U: Iterator<Item = B>,
-//     F: Fn(A) -> U + Clone,
-// {
-//     type HktArg1 = B;
-// }
-
-// impl<A, B, I, U, F> Functor for std::iter::FlatMap<I, U, F>
-// where
-//     I: Iterator<Item = A>,
-//     U: Iterator<Item = B>,
-//     F: Fn(A) -> U + Clone,
-// {
-//     type Map<V, G> = std::iter::Map<Self, G>
-//     where
-//         G: Fn(B) -> V + Clone;
-
-//     #[inline(always)]
-//     fn fmap<V, G>(self, g: G) -> std::iter::Map<Self, G>
-//     where
-//         G: Fn(B) -> V + Clone,
-//     {
-//         self.map(g)
-//     }
-
-//     #[inline(always)]
-//     fn fmap1<G>(self, g: G) -> std::iter::Map<Self, G>
-//     where
-//         G: Fn(B) -> B + Clone,
-//     {
-//         self.map(g)
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-
-//     use super::*;
-
-//     #[test]
-//     fn test_vec_into_iter_functor() {
-//         assert_eq!(
-//             Vec::<i32>::new()
-//                 .into_iter()
-//                 .fmap(&|x: i32| x + 1)
-//                 .collect::<Vec<_>>(),
-//             Vec::<i32>::new()
-//         );
-
-//         let v = vec![1, 2, 3];
-//         assert_eq!(
-//             v.into_iter().fmap(&|x| x + 1).collect::<Vec<_>>(),
-//             vec![2, 3, 4]
-//         );
-//     }
-
-//     #[test]
-//     fn test_map_functor() {
-//         fn f0(x: i32) -> i32 {
-//             x + 1
-//         }
-
-//         let f0_ = |x: i32| x + 1;
-
-//         let map0 = Vec::<i32>::new().into_iter().map(f0);
-//         let map1 = vec![1, 2, 3].into_iter().map(f0_);
-
-//         assert_eq!(map0.fmap(|x| x + 1).collect::<Vec<_>>(), Vec::<i32>::new());
-//         assert_eq!(map1.fmap(|x| x + 1).collect::<Vec<_>>(), vec![3, 4, 5]);
-//     }
-
-//     #[test]
-//     fn test_once_functor() {
-//         let once = std::iter::once(1);
-//         assert_eq!(once.fmap(|x| x + 1).next(), Some(2));
-//     }
-
-//     #[test]
-//     fn test_flat_map_functor() {
-//         let flat_map = vec![1, 2, 3].into_iter().flat_map(|x| vec![x + 1, x + 2]);
-//         assert_eq!(
-//             flat_map.fmap(|x| x + 1).collect::<Vec<_>>(),
-//             vec![3, 4, 4, 5, 5, 6]
-//         );
-//     }
-// }
+use crate::base::hkt::Hkt1;
+
+use super::functor::Functor;
+
+/// Lazy `Functor` for an iterator adaptor.
+///
+/// `fmap` is `Iterator::map`, so nothing is evaluated until the result is consumed.
+macro_rules! impl_iter_functor {
+    (<$($param:ident),*> $iter:ty $(where $($bound:tt)*)?) => {
+        impl<$($param),*> Hkt1 for $iter
+        where
+            Self: Iterator,
+            $($($bound)*)?
+        {
+            type HktArg1 = <Self as Iterator>::Item;
+        }
+
+        impl<$($param),*> Functor for $iter
+        where
+            Self: Iterator,
+            $($($bound)*)?
+        {
+            type Map<C, G> = std::iter::Map<Self, G>
+            where
+                G: Fn(Self::HktArg1) -> C + Clone;
+
+            #[inline(always)]
+            fn fmap<C, G>(self, g: G) -> std::iter::Map<Self, G>
+            where
+                G: Fn(Self::HktArg1) -> C + Clone,
+            {
+                self.map(g)
+            }
+        }
+    };
+}
+
+// Sources
+
+impl_iter_functor!(<A> std::vec::IntoIter<A>);
+impl_iter_functor!(<A> std::option::IntoIter<A>);
+impl_iter_functor!(<A> std::collections::vec_deque::IntoIter<A>);
+impl_iter_functor!(<A> std::ops::Range<A>);
+impl_iter_functor!(<A> std::ops::RangeInclusive<A>);
+impl_iter_functor!(<A> std::ops::RangeFrom<A>);
+impl_iter_functor!(<A> std::iter::Empty<A>);
+impl_iter_functor!(<A> std::iter::Once<A>);
+impl_iter_functor!(<A> std::iter::Repeat<A>);
+impl_iter_functor!(<F> std::iter::FromFn<F>);
+impl_iter_functor!(<A, F> std::iter::Successors<A, F>);
+
+// Adaptors
+
+impl_iter_functor!(<I, F> std::iter::Map<I, F>);
+impl_iter_functor!(<I, U, F> std::iter::FlatMap<I, U, F> where U: IntoIterator);
+impl_iter_functor!(<I> std::iter::Flatten<I> where I: Iterator, I::Item: IntoIterator);
+impl_iter_functor!(<I, P> std::iter::Filter<I, P>);
+impl_iter_functor!(<I, F> std::iter::FilterMap<I, F>);
+impl_iter_functor!(<A, B> std::iter::Chain<A, B>);
+impl_iter_functor!(<A, B> std::iter::Zip<A, B>);
+impl_iter_functor!(<I> std::iter::Take<I>);
+impl_iter_functor!(<I> std::iter::Skip<I>);
+impl_iter_functor!(<I, P> std::iter::TakeWhile<I, P>);
+impl_iter_functor!(<I, P> std::iter::SkipWhile<I, P>);
+impl_iter_functor!(<I> std::iter::StepBy<I>);
+impl_iter_functor!(<I> std::iter::Enumerate<I>);
+impl_iter_functor!(<I> std::iter::Peekable<I> where I: Iterator);
+impl_iter_functor!(<I> std::iter::Rev<I>);
+impl_iter_functor!(<I> std::iter::Cycle<I>);
+impl_iter_functor!(<I> std::iter::Fuse<I>);
+impl_iter_functor!(<I, F> std::iter::Inspect<I, F>);
+impl_iter_functor!(<I, St, F> std::iter::Scan<I, St, F>);
+impl_iter_functor!(<I> std::iter::Cloned<I>);
+impl_iter_functor!(<I> std::iter::Copied<I>);
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_vec_into_iter_functor() {
+        assert_eq!(
+            Vec::<i32>::new()
+                .into_iter()
+                .fmap(&|x: i32| x + 1)
+                .collect::<Vec<_>>(),
+            Vec::<i32>::new()
+        );
+
+        let v = vec![1, 2, 3];
+        assert_eq!(
+            v.into_iter().fmap(&|x| x + 1).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_map_functor() {
+        fn f0(x: i32) -> i32 {
+            x + 1
+        }
+
+        let f0_ = |x: i32| x + 1;
+
+        let map0 = Vec::<i32>::new().into_iter().map(f0);
+        let map1 = vec![1, 2, 3].into_iter().map(f0_);
+
+        assert_eq!(map0.fmap(|x| x + 1).collect::<Vec<_>>(), Vec::<i32>::new());
+        assert_eq!(map1.fmap(|x| x + 1).collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_once_functor() {
+        let once = std::iter::once(1);
+        assert_eq!(once.fmap(|x| x + 1).next(), Some(2));
+    }
+
+    #[test]
+    fn test_flat_map_functor() {
+        let flat_map = vec![1, 2, 3].into_iter().flat_map(|x| vec![x + 1, x + 2]);
+        assert_eq!(
+            flat_map.fmap(|x| x + 1).collect::<Vec<_>>(),
+            vec![3, 4, 4, 5, 5, 6]
+        );
+    }
+
+    #[test]
+    fn test_adaptor_functors() {
+        let filter = (0..10).filter(|x| x % 2 == 0);
+        assert_eq!(
+            filter.fmap(|x| x * 10).collect::<Vec<_>>(),
+            vec![0, 20, 40, 60, 80]
+        );
+
+        let chain = vec![1, 2].into_iter().chain(vec![3]);
+        assert_eq!(chain.fmap(|x| x + 1).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        let zip = vec![1, 2, 3].into_iter().zip(vec!['a', 'b']);
+        assert_eq!(
+            zip.fmap(|(n, c)| format!("{}{}", c, n)).collect::<Vec<_>>(),
+            vec!["a1", "b2"]
+        );
+
+        let take = (1..).take(3);
+        assert_eq!(take.fmap(|x| x + 1).collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_generic_functor_is_lazy() {
+        fn double<Fa>(fa: Fa) -> Fa::Map<i32, fn(i32) -> i32>
+        where
+            Fa: Functor<HktArg1 = i32>,
+        {
+            fa.fmap(|x| x * 2)
+        }
+
+        let evaluated = std::cell::Cell::new(0);
+        let pipeline = (1..)
+            .inspect(|_| evaluated.set(evaluated.get() + 1))
+            .skip(1)
+            .step_by(2);
+
+        let mut doubled = double(pipeline);
+        assert_eq!(evaluated.get(), 0);
+
+        assert_eq!(doubled.next(), Some(4));
+        assert_eq!(doubled.next(), Some(8));
+        assert_eq!(evaluated.get(), 4);
+    }
+}
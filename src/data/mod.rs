@@ -1,8 +1,13 @@
 pub mod applicative;
+pub mod foldable;
 pub mod functor;
 pub mod monad;
 pub mod monoid;
+pub mod traversable;
 pub mod tree;
 
-// pub mod iter;
+pub mod either;
+pub mod iter;
 pub mod option;
+pub mod result;
+pub mod vec;
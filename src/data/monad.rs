@@ -1,52 +1,129 @@
-// use super::applicative::Applicative;
-// use super::functor::Functor;
+use crate::base::hkt::Hkt1;
 
-// use super::functor::Functor;
+use super::applicative::Applicative;
 
-// ! Not useful until the HKT family is implemented
+pub trait Monad: Applicative {
+    /// Sequentially compose two actions, passing the result of the first to the second
+    ///
+    /// m a -> (a -> m b) -> m b
+    fn bind<B, F>(self, f: F) -> Self::Of<B>
+    where
+        F: Fn(Self::HktArg1) -> Self::Of<B> + Clone;
 
-// pub trait Monad: Functor {
-//     type Pure<A_>: Monad<HktArg1 = A_>;
+    /// Sequentially compose two actions, discarding the result of the first
+    ///
+    /// m a -> m b -> m b
+    fn then<B>(self, mb: Self::Of<B>) -> Self::Of<B>
+    where
+        B: Clone,
+    {
+        self.lift_a2(mb, |_, b| b)
+    }
 
-//     type M<B_>: Monad<HktArg1 = B_>;
+    /// Remove one level of monadic structure
+    ///
+    /// m (m a) -> m a
+    fn join<A>(self) -> Self::Of<A>
+    where
+        Self: Hkt1<HktArg1 = Self::Of<A>>,
+    {
+        self.bind(|ma| ma)
+    }
+}
 
-//     type Bind<B_, Mf_>: Monad<HktArg1 = B_>
-//     where
-//         Mf_: Fn(Self::HktArg1) -> Self::M<B_> + Clone;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tree::Tree;
 
-//     fn pure<A>(a: A) -> Self::Pure<A>;
+    // Monad laws
 
-//     fn bind<B, Mf_>(self, mf: Mf_) -> Self::Bind<B, Mf_>
-//     where
-//         Mf_: Fn(Self::HktArg1) -> Self::M<B> + Clone;
-// }
+    #[test]
+    fn test_left_identity_law() {
+        let f = |x: i32| if x > 0 { Some(x * 2) } else { None };
+        assert_eq!(Option::pure(3).bind(f), f(3));
+        assert_eq!(Option::pure(-3).bind(f), f(-3));
 
-// #[cfg(test)]
-// mod tests {
+        let g = |x: i32| if x > 0 { Ok(x * 2) } else { Err(x) };
+        assert_eq!(Result::pure(3).bind(g), g(3));
+        assert_eq!(Result::pure(-3).bind(g), g(-3));
 
-//     use super::*;
+        let h = |x: i32| vec![x, x + 1];
+        assert_eq!(Vec::pure(3).bind(h), h(3));
 
-//     impl<T> Monad for Option<T> {
-//         fn and_then<B, F>(self, f: F) -> Self::Of<B>
-//         where
-//             F: Fn(T) -> Self::Of<B>,
-//         {
-//             match self {
-//                 Some(x) => f(x),
-//                 None => None,
-//             }
-//         }
-//     }
+        let t = |x: i32| Tree {
+            value: x,
+            children: vec![Tree::pure(x + 1)],
+        };
+        assert_eq!(Tree::pure(3).bind(t), t(3));
+    }
 
-//     #[test]
-//     fn test_option_monad() {
-//         assert_eq!(None.and_then(|x: i32| Some(x + 1)), None);
-//         assert_eq!(Some(1).and_then(|x: i32| Some(x + 1)), Some(2));
+    #[test]
+    fn test_right_identity_law() {
+        assert_eq!(Some(1).bind(Option::pure), Some(1));
+        assert_eq!(None::<i32>.bind(Option::pure), None);
 
-//         assert_eq!(None::<i32>.then(None::<i32>), None);
-//         assert_eq!(None::<i32>.then(Some(1)), None);
+        assert_eq!(Ok::<i32, ()>(1).bind(Result::pure), Ok(1));
+        assert_eq!(Err::<i32, ()>(()).bind(Result::pure), Err(()));
 
-//         assert_eq!(Some(1).then(None::<i32>), None);
-//         assert_eq!(Some(1).then(Some(2)), Some(2));
-//     }
-// }
+        assert_eq!(vec![1, 2, 3].bind(Vec::pure), vec![1, 2, 3]);
+
+        let tree = Tree {
+            value: 1,
+            children: vec![Tree::pure(2), Tree::pure(3)],
+        };
+        assert_eq!(tree.clone().bind(Tree::pure), tree);
+    }
+
+    #[test]
+    fn test_associativity_law() {
+        let f = |x: i32| if x > 0 { Some(x - 1) } else { None };
+        let g = |x: i32| if x % 2 == 0 { Some(x / 2) } else { None };
+        for m in [None, Some(0), Some(1), Some(5)] {
+            assert_eq!(m.bind(f).bind(g), m.bind(move |x| f(x).bind(g)));
+        }
+
+        let h = |x: i32| vec![x, x * 10];
+        let k = |x: i32| vec![x + 1, x + 2];
+        let m = vec![1, 2];
+        assert_eq!(m.clone().bind(h).bind(k), m.bind(move |x| h(x).bind(k)));
+
+        let s = |x: i32| Tree {
+            value: x,
+            children: vec![Tree::pure(x * 10)],
+        };
+        let t = |x: i32| Tree {
+            value: x + 1,
+            children: vec![Tree::pure(x + 2)],
+        };
+        let tree = Tree {
+            value: 1,
+            children: vec![Tree::pure(2)],
+        };
+        assert_eq!(
+            tree.clone().bind(s).bind(t),
+            tree.bind(move |x| s(x).bind(t))
+        );
+    }
+
+    #[test]
+    fn test_then() {
+        assert_eq!(None::<i32>.then(None::<i32>), None);
+        assert_eq!(None::<i32>.then(Some(1)), None);
+
+        assert_eq!(Some(1).then(None::<i32>), None);
+        assert_eq!(Some(1).then(Some(2)), Some(2));
+
+        assert_eq!(vec![1, 2].then(vec!['a', 'b']), vec!['a', 'b', 'a', 'b']);
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(Some(Some(1)).join(), Some(1));
+        assert_eq!(Some(None::<i32>).join(), None);
+
+        assert_eq!(Ok::<Result<i32, ()>, ()>(Ok(1)).join(), Ok(1));
+
+        assert_eq!(vec![vec![1, 2], vec![3]].join(), vec![1, 2, 3]);
+    }
+}
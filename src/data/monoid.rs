@@ -1,7 +1,496 @@
-pub trait Monoid {
+use std::collections::BTreeMap;
+
+pub trait Semigroup {
     type A;
 
+    /// An associative binary operation
+    fn mappend(lhs: Self::A, rhs: Self::A) -> Self::A;
+}
+
+pub trait Monoid: Semigroup {
+    /// Identity of `mappend`
     fn mempty() -> Self::A;
+}
 
-    fn mappend(lhs: Self::A, rhs: Self::A) -> Self::A;
+/// Combine all the values of an iterable with `mappend`
+/// Returns `mempty` for empty iterables.
+pub fn mconcat<M>(xs: impl IntoIterator<Item = M::A>) -> M::A
+where
+    M: Monoid,
+{
+    xs.into_iter().fold(M::mempty(), M::mappend)
+}
+
+/// Combine all the values of an iterable with `mappend`
+/// Returns None for empty iterables.
+pub fn sconcat<S>(xs: impl IntoIterator<Item = S::A>) -> Option<S::A>
+where
+    S: Semigroup,
+{
+    xs.into_iter().reduce(S::mappend)
+}
+
+// Instances
+
+impl Semigroup for () {
+    type A = ();
+
+    fn mappend(_: (), _: ()) {}
+}
+
+impl Monoid for () {
+    fn mempty() {}
+}
+
+impl Semigroup for String {
+    type A = String;
+
+    fn mappend(mut lhs: String, rhs: String) -> String {
+        lhs.push_str(&rhs);
+        lhs
+    }
+}
+
+impl Monoid for String {
+    fn mempty() -> String {
+        String::new()
+    }
+}
+
+impl<T> Semigroup for Vec<T> {
+    type A = Vec<T>;
+
+    fn mappend(mut lhs: Vec<T>, rhs: Vec<T>) -> Vec<T> {
+        lhs.extend(rhs);
+        lhs
+    }
+}
+
+impl<T> Monoid for Vec<T> {
+    fn mempty() -> Vec<T> {
+        Vec::new()
+    }
+}
+
+/// Lifts a semigroup into a monoid with `None` as identity
+impl<S> Semigroup for Option<S>
+where
+    S: Semigroup,
+{
+    type A = Option<S::A>;
+
+    fn mappend(lhs: Option<S::A>, rhs: Option<S::A>) -> Option<S::A> {
+        match (lhs, rhs) {
+            (Some(a), Some(b)) => Some(S::mappend(a, b)),
+            (a, None) => a,
+            (None, b) => b,
+        }
+    }
+}
+
+impl<S> Monoid for Option<S>
+where
+    S: Semigroup,
+{
+    fn mempty() -> Option<S::A> {
+        None
+    }
+}
+
+/// Left-biased union
+impl<K, V> Semigroup for BTreeMap<K, V>
+where
+    K: Ord,
+{
+    type A = BTreeMap<K, V>;
+
+    fn mappend(mut lhs: BTreeMap<K, V>, rhs: BTreeMap<K, V>) -> BTreeMap<K, V> {
+        for (k, v) in rhs {
+            lhs.entry(k).or_insert(v);
+        }
+        lhs
+    }
+}
+
+impl<K, V> Monoid for BTreeMap<K, V>
+where
+    K: Ord,
+{
+    fn mempty() -> BTreeMap<K, V> {
+        BTreeMap::new()
+    }
+}
+
+macro_rules! impl_tuple_monoid {
+    ($($s:ident $lhs:ident $rhs:ident),+) => {
+        impl<$($s),+> Semigroup for ($($s,)+)
+        where
+            $($s: Semigroup),+
+        {
+            type A = ($($s::A,)+);
+
+            fn mappend(($($lhs,)+): Self::A, ($($rhs,)+): Self::A) -> Self::A {
+                ($($s::mappend($lhs, $rhs),)+)
+            }
+        }
+
+        impl<$($s),+> Monoid for ($($s,)+)
+        where
+            $($s: Monoid),+
+        {
+            fn mempty() -> Self::A {
+                ($($s::mempty(),)+)
+            }
+        }
+    };
+}
+
+impl_tuple_monoid!(S1 a1 b1, S2 a2 b2);
+impl_tuple_monoid!(S1 a1 b1, S2 a2 b2, S3 a3 b3);
+impl_tuple_monoid!(S1 a1 b1, S2 a2 b2, S3 a3 b3, S4 a4 b4);
+
+// Newtypes
+
+/// Monoid under addition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Sum<T>(pub T);
+
+impl<T> Semigroup for Sum<T>
+where
+    T: std::ops::Add<Output = T>,
+{
+    type A = Sum<T>;
+
+    fn mappend(lhs: Sum<T>, rhs: Sum<T>) -> Sum<T> {
+        Sum(lhs.0 + rhs.0)
+    }
+}
+
+impl<T> Monoid for Sum<T>
+where
+    T: std::ops::Add<Output = T> + num_traits::Zero,
+{
+    fn mempty() -> Sum<T> {
+        Sum(T::zero())
+    }
+}
+
+/// Monoid under multiplication
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Product<T>(pub T);
+
+impl<T> Default for Product<T>
+where
+    T: std::ops::Mul<Output = T> + num_traits::One,
+{
+    fn default() -> Self {
+        Product::mempty()
+    }
+}
+
+impl<T> Semigroup for Product<T>
+where
+    T: std::ops::Mul<Output = T>,
+{
+    type A = Product<T>;
+
+    fn mappend(lhs: Product<T>, rhs: Product<T>) -> Product<T> {
+        Product(lhs.0 * rhs.0)
+    }
+}
+
+impl<T> Monoid for Product<T>
+where
+    T: std::ops::Mul<Output = T> + num_traits::One,
+{
+    fn mempty() -> Product<T> {
+        Product(T::one())
+    }
+}
+
+/// Monoid under `min`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Min<T>(pub T);
+
+impl<T> Default for Min<T>
+where
+    T: Ord + num_traits::Bounded,
+{
+    fn default() -> Self {
+        Min::mempty()
+    }
+}
+
+impl<T> Semigroup for Min<T>
+where
+    T: Ord,
+{
+    type A = Min<T>;
+
+    fn mappend(lhs: Min<T>, rhs: Min<T>) -> Min<T> {
+        Min(lhs.0.min(rhs.0))
+    }
+}
+
+impl<T> Monoid for Min<T>
+where
+    T: Ord + num_traits::Bounded,
+{
+    fn mempty() -> Min<T> {
+        Min(T::max_value())
+    }
+}
+
+/// Monoid under `max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Max<T>(pub T);
+
+impl<T> Default for Max<T>
+where
+    T: Ord + num_traits::Bounded,
+{
+    fn default() -> Self {
+        Max::mempty()
+    }
+}
+
+impl<T> Semigroup for Max<T>
+where
+    T: Ord,
+{
+    type A = Max<T>;
+
+    fn mappend(lhs: Max<T>, rhs: Max<T>) -> Max<T> {
+        Max(lhs.0.max(rhs.0))
+    }
+}
+
+impl<T> Monoid for Max<T>
+where
+    T: Ord + num_traits::Bounded,
+{
+    fn mempty() -> Max<T> {
+        Max(T::min_value())
+    }
+}
+
+/// Monoid returning the leftmost non-None value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct First<T>(pub Option<T>);
+
+impl<T> Semigroup for First<T> {
+    type A = First<T>;
+
+    fn mappend(lhs: First<T>, rhs: First<T>) -> First<T> {
+        First(lhs.0.or(rhs.0))
+    }
+}
+
+impl<T> Monoid for First<T> {
+    fn mempty() -> First<T> {
+        First(None)
+    }
+}
+
+/// Monoid returning the rightmost non-None value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Last<T>(pub Option<T>);
+
+impl<T> Semigroup for Last<T> {
+    type A = Last<T>;
+
+    fn mappend(lhs: Last<T>, rhs: Last<T>) -> Last<T> {
+        Last(rhs.0.or(lhs.0))
+    }
+}
+
+impl<T> Monoid for Last<T> {
+    fn mempty() -> Last<T> {
+        Last(None)
+    }
+}
+
+/// Boolean monoid under disjunction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Any(pub bool);
+
+impl Semigroup for Any {
+    type A = Any;
+
+    fn mappend(lhs: Any, rhs: Any) -> Any {
+        Any(lhs.0 || rhs.0)
+    }
+}
+
+impl Monoid for Any {
+    fn mempty() -> Any {
+        Any(false)
+    }
+}
+
+/// Boolean monoid under conjunction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct All(pub bool);
+
+impl Default for All {
+    fn default() -> Self {
+        All(true)
+    }
+}
+
+impl Semigroup for All {
+    type A = All;
+
+    fn mappend(lhs: All, rhs: All) -> All {
+        All(lhs.0 && rhs.0)
+    }
+}
+
+impl Monoid for All {
+    fn mempty() -> All {
+        All(true)
+    }
+}
+
+/// Monoid of endomorphisms under composition
+pub struct Endo<A>(pub Box<dyn Fn(A) -> A>);
+
+impl<A> Endo<A> {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(A) -> A + 'static,
+    {
+        Endo(Box::new(f))
+    }
+
+    pub fn app_endo(&self, a: A) -> A {
+        (self.0)(a)
+    }
+}
+
+impl<A> Semigroup for Endo<A>
+where
+    A: 'static,
+{
+    type A = Endo<A>;
+
+    /// `lhs . rhs`, so `rhs` is applied first
+    fn mappend(lhs: Endo<A>, rhs: Endo<A>) -> Endo<A> {
+        Endo(Box::new(move |a| (lhs.0)((rhs.0)(a))))
+    }
+}
+
+impl<A> Monoid for Endo<A>
+where
+    A: 'static,
+{
+    fn mempty() -> Endo<A> {
+        Endo(Box::new(|a| a))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mconcat() {
+        assert_eq!(mconcat::<String>(Vec::<String>::new()), "");
+        assert_eq!(
+            mconcat::<String>(vec!["ab".to_string(), "c".to_string()]),
+            "abc"
+        );
+        assert_eq!(
+            mconcat::<Vec<i32>>(vec![vec![1], vec![2, 3]]),
+            vec![1, 2, 3]
+        );
+
+        // Works over any iterator, not just collections
+        assert_eq!(mconcat::<Sum<i32>>((1..=4).map(Sum)), Sum(10));
+        assert_eq!(mconcat::<Product<i32>>((1..=4).map(Product)), Product(24));
+    }
+
+    #[test]
+    fn test_sconcat() {
+        assert_eq!(sconcat::<Max<i32>>(Vec::new()), None);
+        assert_eq!(sconcat::<Max<i32>>(vec![Max(1), Max(3)]), Some(Max(3)));
+    }
+
+    #[test]
+    fn test_option_monoid() {
+        assert_eq!(Option::<Sum<i32>>::mappend(None, None), None);
+        assert_eq!(
+            Option::<Sum<i32>>::mappend(Some(Sum(1)), None),
+            Some(Sum(1))
+        );
+        assert_eq!(
+            Option::<Sum<i32>>::mappend(Some(Sum(1)), Some(Sum(2))),
+            Some(Sum(3))
+        );
+    }
+
+    #[test]
+    fn test_tuple_monoid() {
+        assert_eq!(
+            mconcat::<(Sum<i32>, Product<i32>, String)>(vec![
+                (Sum(1), Product(2), "a".to_string()),
+                (Sum(3), Product(4), "b".to_string()),
+            ]),
+            (Sum(4), Product(8), "ab".to_string())
+        );
+    }
+
+    #[test]
+    fn test_btree_map_union() {
+        let lhs = BTreeMap::from([(1, "a"), (2, "b")]);
+        let rhs = BTreeMap::from([(2, "x"), (3, "c")]);
+
+        assert_eq!(
+            BTreeMap::mappend(lhs, rhs),
+            BTreeMap::from([(1, "a"), (2, "b"), (3, "c")])
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert_eq!(mconcat::<Min<i32>>(Vec::new()), Min(i32::MAX));
+        assert_eq!(mconcat::<Min<i32>>(vec![Min(3), Min(1)]), Min(1));
+        assert_eq!(mconcat::<Max<i32>>(vec![Max(3), Max(1)]), Max(3));
+    }
+
+    #[test]
+    fn test_default_is_mempty() {
+        assert_eq!(Sum::<i32>::default(), Sum::mempty());
+        assert_eq!(Product::<i32>::default(), Product(1));
+        assert_eq!(Min::<u8>::default(), Min(u8::MAX));
+        assert_eq!(Max::<i8>::default(), Max(i8::MIN));
+        assert_eq!(All::default(), All::mempty());
+    }
+
+    #[test]
+    fn test_first_last() {
+        let xs = || vec![First(None), First(Some(1)), First(Some(2))];
+        assert_eq!(mconcat::<First<i32>>(xs()), First(Some(1)));
+
+        let ys = vec![Last(Some(1)), Last(Some(2)), Last(None)];
+        assert_eq!(mconcat::<Last<i32>>(ys), Last(Some(2)));
+    }
+
+    #[test]
+    fn test_any_all() {
+        assert_eq!(mconcat::<Any>(Vec::new()), Any(false));
+        assert_eq!(mconcat::<Any>(vec![Any(false), Any(true)]), Any(true));
+
+        assert_eq!(mconcat::<All>(Vec::new()), All(true));
+        assert_eq!(mconcat::<All>(vec![All(true), All(false)]), All(false));
+    }
+
+    #[test]
+    fn test_endo() {
+        let f = mconcat::<Endo<i32>>(vec![Endo::new(|x| x + 1), Endo::new(|x| x * 2)]);
+
+        // (+1) . (*2)
+        assert_eq!(f.app_endo(3), 7);
+        assert_eq!(Endo::<i32>::mempty().app_endo(3), 3);
+    }
 }
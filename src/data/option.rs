@@ -1,7 +1,11 @@
 use crate::base::hkt::Hkt1;
 use crate::impl_hkt1;
 
+use super::applicative::Applicative;
+use super::foldable::Foldable;
 use super::functor::Functor;
+use super::monad::Monad;
+use super::traversable::Traversable;
 
 impl_hkt1!(Option);
 
@@ -19,6 +23,105 @@ impl<A> Functor for Option<A> {
     }
 }
 
+impl<A> Applicative for Option<A> {
+    type Of<T> = Option<T>;
+
+    #[inline(always)]
+    fn pure(a: A) -> Self {
+        Some(a)
+    }
+
+    #[inline(always)]
+    fn lift_a<B, F>(self, f: F) -> Option<B>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        self.map(f)
+    }
+
+    #[inline(always)]
+    fn apply<B, F>(self, ff: Option<F>) -> Option<B>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        match (self, ff) {
+            (Some(a), Some(f)) => Some(f(a)),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn lift_a2<B, C, F>(self, fb: Option<B>, f: F) -> Option<C>
+    where
+        B: Clone,
+        F: Fn(A, B) -> C + Clone,
+    {
+        match (self, fb) {
+            (Some(a), Some(b)) => Some(f(a, b)),
+            _ => None,
+        }
+    }
+}
+
+impl<A> Monad for Option<A> {
+    #[inline(always)]
+    fn bind<B, F>(self, f: F) -> Option<B>
+    where
+        F: Fn(A) -> Option<B> + Clone,
+    {
+        self.and_then(f)
+    }
+}
+
+impl<A> Foldable for Option<A> {
+    #[inline(always)]
+    fn foldr<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(A, B) -> B,
+    {
+        match self {
+            Some(a) => f(a, init),
+            None => init,
+        }
+    }
+
+    #[inline(always)]
+    fn foldl<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(B, A) -> B,
+    {
+        match self {
+            Some(a) => f(init, a),
+            None => init,
+        }
+    }
+}
+
+impl<A> Traversable for Option<A> {
+    type Of<T> = Option<T>;
+
+    #[inline(always)]
+    fn traverse_with<B, F>(self, f: F) -> Option<B>
+    where
+        F: FnMut(A) -> B,
+    {
+        self.map(f)
+    }
+
+    fn traverse<B, Fb, F>(self, f: F) -> Fb::Of<Option<B>>
+    where
+        Fb: Applicative<HktArg1 = B>,
+        Fb::Of<Option<B>>: Applicative<HktArg1 = Option<B>, Of<Option<B>> = Fb::Of<Option<B>>>,
+        Option<B>: Clone,
+        F: Fn(A) -> Fb,
+    {
+        match self {
+            Some(a) => f(a).lift_a(Some),
+            None => <Fb::Of<Option<B>>>::pure(None),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -29,4 +132,19 @@ mod tests {
         assert_eq!(None.fmap(|x: i32| x + 1), None);
         assert_eq!(Some(1).fmap(|x: i32| x + 1), Some(2));
     }
+
+    #[test]
+    fn test_option_apply() {
+        assert_eq!(None::<i32>.apply(None::<fn(i32) -> i32>), None);
+        assert_eq!(None.apply(Some(|x: i32| x + 1)), None);
+
+        assert_eq!(Some(1).apply(None::<fn(i32) -> i32>), None);
+        assert_eq!(Some(1).apply(Some(|x: i32| x + 1)), Some(2));
+    }
+
+    #[test]
+    fn test_option_bind() {
+        assert_eq!(None.bind(|x: i32| Some(x + 1)), None);
+        assert_eq!(Some(1).bind(|x: i32| Some(x + 1)), Some(2));
+    }
 }
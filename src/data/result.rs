@@ -0,0 +1,78 @@
+use crate::base::hkt::Hkt1;
+
+use super::applicative::Applicative;
+use super::functor::Functor;
+use super::monad::Monad;
+
+impl<T, E> Hkt1 for Result<T, E> {
+    type HktArg1 = T;
+}
+
+impl<A, E> Functor for Result<A, E> {
+    type Map<B, F> = Result<B, E>
+    where
+        F: Fn(A) -> B + Clone;
+
+    #[inline(always)]
+    fn fmap<B, F>(self, f: F) -> Result<B, E>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        self.map(f)
+    }
+}
+
+impl<A, E> Applicative for Result<A, E> {
+    type Of<T> = Result<T, E>;
+
+    #[inline(always)]
+    fn pure(a: A) -> Self {
+        Ok(a)
+    }
+
+    #[inline(always)]
+    fn lift_a<B, F>(self, f: F) -> Result<B, E>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        self.map(f)
+    }
+
+    #[inline(always)]
+    fn apply<B, F>(self, ff: Result<F, E>) -> Result<B, E>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        ff.and_then(|f| self.map(f))
+    }
+
+    #[inline(always)]
+    fn lift_a2<B, C, F>(self, fb: Result<B, E>, f: F) -> Result<C, E>
+    where
+        B: Clone,
+        F: Fn(A, B) -> C + Clone,
+    {
+        self.and_then(|a| fb.map(|b| f(a, b)))
+    }
+}
+
+impl<A, E> Monad for Result<A, E> {
+    #[inline(always)]
+    fn bind<B, F>(self, f: F) -> Result<B, E>
+    where
+        F: Fn(A) -> Result<B, E> + Clone,
+    {
+        self.and_then(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_functor() {
+        assert_eq!(Err::<i32, ()>(()).fmap(|x| x + 1), Err(()));
+        assert_eq!(Ok::<i32, ()>(1).fmap(|x| x + 1), Ok(2));
+    }
+}
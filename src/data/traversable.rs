@@ -0,0 +1,179 @@
+use crate::prelude::Io;
+
+use super::applicative::Applicative;
+use super::foldable::Foldable;
+use super::functor::Functor;
+
+pub trait Traversable: Functor + Foldable {
+    /// The same type constructor applied to another argument
+    type Of<T>;
+
+    /// Map each element in order with a stateful function, keeping the shape
+    fn traverse_with<B, F>(self, f: F) -> Self::Of<B>
+    where
+        F: FnMut(Self::HktArg1) -> B;
+
+    /// Map each element to an action, evaluate them from left to right and collect the results
+    ///
+    /// Applicative f => t a -> (a -> f b) -> f (t b)
+    fn traverse<B, Fb, F>(self, f: F) -> Fb::Of<Self::Of<B>>
+    where
+        Fb: Applicative<HktArg1 = B>,
+        Fb::Of<Self::Of<B>>:
+            Applicative<HktArg1 = Self::Of<B>, Of<Self::Of<B>> = Fb::Of<Self::Of<B>>>,
+        Self::Of<B>: Clone,
+        F: Fn(Self::HktArg1) -> Fb;
+
+    /// Evaluate each action from left to right and collect the results
+    ///
+    /// Applicative f => t (f a) -> f (t a)
+    fn sequence<B>(self) -> <Self::HktArg1 as Applicative>::Of<Self::Of<B>>
+    where
+        Self::HktArg1: Applicative<HktArg1 = B>,
+        <Self::HktArg1 as Applicative>::Of<Self::Of<B>>: Applicative<
+            HktArg1 = Self::Of<B>,
+            Of<Self::Of<B>> = <Self::HktArg1 as Applicative>::Of<Self::Of<B>>,
+        >,
+        Self::Of<B>: Clone,
+    {
+        self.traverse(|fb| fb)
+    }
+
+    /// `traverse` specialized to `Io`
+    ///
+    /// t a -> (a -> IO b) -> IO (t b)
+    fn traverse_io<I, F>(self, f: F) -> TraverseIo<Self, F>
+    where
+        I: Io,
+        F: Fn(Self::HktArg1) -> I,
+    {
+        TraverseIo { xs: self, f }
+    }
+
+    /// `sequence` specialized to `Io`
+    ///
+    /// t (IO a) -> IO (t a)
+    fn sequence_io(self) -> SequenceIo<Self>
+    where
+        Self::HktArg1: Io,
+    {
+        SequenceIo { xs: self }
+    }
+}
+
+#[derive(Clone)]
+pub struct TraverseIo<T, F> {
+    xs: T,
+    f: F,
+}
+
+impl<T, I, F> Io for TraverseIo<T, F>
+where
+    T: Traversable,
+    I: Io,
+    F: Fn(T::HktArg1) -> I,
+{
+    type Output = T::Of<I::Output>;
+
+    fn run(self) -> Self::Output {
+        let f = self.f;
+        self.xs.traverse_with(|a| f(a).run())
+    }
+}
+
+#[derive(Clone)]
+pub struct SequenceIo<T> {
+    xs: T,
+}
+
+impl<T> Io for SequenceIo<T>
+where
+    T: Traversable,
+    T::HktArg1: Io,
+{
+    type Output = T::Of<<T::HktArg1 as Io>::Output>;
+
+    fn run(self) -> Self::Output {
+        self.xs.traverse_with(|io| io.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tree::Tree;
+    use crate::prelude::from_result;
+
+    #[test]
+    fn test_traverse() {
+        let half = |x: i32| if x % 2 == 0 { Some(x / 2) } else { None };
+
+        assert_eq!(vec![2, 4, 6].traverse(half), Some(vec![1, 2, 3]));
+        assert_eq!(vec![2, 3, 6].traverse(half), None);
+        assert_eq!(Some(4).traverse(half), Some(Some(2)));
+        assert_eq!(None.traverse(half), Some(None));
+
+        let tree = Tree {
+            value: 2,
+            children: vec![Tree::pure(4)],
+        };
+        assert_eq!(
+            tree.traverse(half),
+            Some(Tree {
+                value: 1,
+                children: vec![Tree::pure(2)],
+            })
+        );
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(vec![Some(1), Some(2)].sequence(), Some(vec![1, 2]));
+        assert_eq!(vec![Some(1), None].sequence(), None);
+
+        assert_eq!(
+            vec![Ok(1), Err("a"), Err("b")].sequence(),
+            Err::<Vec<i32>, _>("a")
+        );
+
+        // Vec as the applicative: cartesian product
+        assert_eq!(
+            vec![vec![1, 2], vec![3, 4]].sequence(),
+            vec![vec![1, 3], vec![1, 4], vec![2, 3], vec![2, 4]]
+        );
+    }
+
+    #[test]
+    fn test_sequence_io() {
+        let ios = vec![
+            from_result::<i32, ()>(Ok(1)),
+            from_result(Err(())),
+            from_result(Ok(3)),
+        ];
+
+        assert_eq!(ios.sequence_io().run(), vec![Ok(1), Err(()), Ok(3)]);
+    }
+
+    #[test]
+    fn test_traverse_io() {
+        let order = std::cell::RefCell::new(Vec::new());
+
+        let tree = Tree {
+            value: 1,
+            children: vec![Tree::pure(2), Tree::pure(3)],
+        };
+        let io = tree.traverse_io(|x| {
+            order.borrow_mut().push(x);
+            from_result::<_, ()>(Ok(x * 10))
+        });
+
+        assert_eq!(
+            io.run(),
+            Tree {
+                value: Ok(10),
+                children: vec![Tree::pure(Ok(20)), Tree::pure(Ok(30))],
+            }
+        );
+        assert_eq!(*order.borrow(), vec![1, 2, 3]);
+    }
+}
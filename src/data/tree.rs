@@ -1,6 +1,9 @@
 use crate::{
     base::{hkt::Hkt1, misc::in_place},
-    data::functor::Functor,
+    data::{
+        applicative::Applicative, foldable::Foldable, functor::Functor, monad::Monad,
+        traversable::Traversable,
+    },
     impl_hkt1,
 };
 
@@ -70,6 +73,67 @@ impl<T> Tree<T> {
                 .collect(),
         }
     }
+
+    fn bind_ref_f<B, F>(self, f: &F) -> Tree<B>
+    where
+        F: Fn(T) -> Tree<B> + Clone,
+    {
+        let Tree {
+            value,
+            mut children,
+        } = f(self.value);
+
+        children.extend(self.children.into_iter().map(|child| child.bind_ref_f(f)));
+
+        Tree { value, children }
+    }
+
+    fn foldr_ref_f<B, F>(self, f: &F, init: B) -> B
+    where
+        F: Fn(T, B) -> B,
+    {
+        let acc = self
+            .children
+            .into_iter()
+            .rev()
+            .fold(init, |acc, child| child.foldr_ref_f(f, acc));
+
+        f(self.value, acc)
+    }
+
+    fn traverse_with_mut_f<B, F>(self, f: &mut F) -> Tree<B>
+    where
+        F: FnMut(T) -> B,
+    {
+        let value = f(self.value);
+        let children = self
+            .children
+            .into_iter()
+            .map(|child| child.traverse_with_mut_f(f))
+            .collect();
+
+        Tree { value, children }
+    }
+
+    fn traverse_ref_f<B, Fb, F>(self, f: &F) -> Fb::Of<Tree<B>>
+    where
+        Fb: Applicative<HktArg1 = B>,
+        Fb::Of<Tree<B>>: Applicative<HktArg1 = Tree<B>, Of<Tree<B>> = Fb::Of<Tree<B>>>,
+        Tree<B>: Clone,
+        F: Fn(T) -> Fb,
+    {
+        let mut acc = f(self.value).lift_a(|value| Tree {
+            value,
+            children: vec![],
+        });
+        for child in self.children {
+            acc = acc.lift_a2(child.traverse_ref_f(f), |mut tree, child: Tree<B>| {
+                tree.children.push(child);
+                tree
+            });
+        }
+        acc
+    }
 }
 
 // Implement Iterator for TreeIter
@@ -144,6 +208,119 @@ impl<T> Functor for Tree<T> {
     }
 }
 
+impl<T> Applicative for Tree<T>
+where
+    T: Clone,
+{
+    type Of<U> = Tree<U>;
+
+    #[inline(always)]
+    fn pure(a: T) -> Self {
+        Tree {
+            value: a,
+            children: vec![],
+        }
+    }
+
+    #[inline(always)]
+    fn lift_a<B, F>(self, f: F) -> Tree<B>
+    where
+        F: Fn(T) -> B + Clone,
+    {
+        self.fmap_ref_f(&f)
+    }
+
+    /// `Node f tfs <*> tx@(Node x txs) = Node (f x) (map (f <$>) txs ++ map (<*> tx) tfs)`
+    fn apply<B, F>(self, ff: Tree<F>) -> Tree<B>
+    where
+        F: Fn(T) -> B + Clone,
+    {
+        let Tree {
+            value: f,
+            children: tfs,
+        } = ff;
+
+        let mut children: Vec<Tree<B>> = self
+            .children
+            .iter()
+            .cloned()
+            .map(|child| child.fmap_ref_f(&f))
+            .collect();
+        children.extend(tfs.into_iter().map(|tf| self.clone().apply(tf)));
+
+        Tree {
+            value: f(self.value),
+            children,
+        }
+    }
+
+    fn lift_a2<B, C, F>(self, fb: Tree<B>, f: F) -> Tree<C>
+    where
+        B: Clone,
+        F: Fn(T, B) -> C + Clone,
+    {
+        fb.apply(self.fmap(move |a: T| {
+            let f = f.clone();
+            move |b: B| f(a.clone(), b)
+        }))
+    }
+}
+
+impl<T> Monad for Tree<T>
+where
+    T: Clone,
+{
+    #[inline(always)]
+    fn bind<B, F>(self, f: F) -> Tree<B>
+    where
+        F: Fn(T) -> Tree<B> + Clone,
+    {
+        self.bind_ref_f(&f)
+    }
+}
+
+impl<T> Foldable for Tree<T> {
+    #[inline(always)]
+    fn foldr<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(T, B) -> B,
+    {
+        self.foldr_ref_f(&f, init)
+    }
+
+    /// Folds in pre-order, the same order as `into_iter`
+    #[inline(always)]
+    fn foldl<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(B, T) -> B,
+    {
+        self.into_iter().fold(init, f)
+    }
+}
+
+impl<T> Traversable for Tree<T> {
+    type Of<U> = Tree<U>;
+
+    #[inline(always)]
+    fn traverse_with<B, F>(self, mut f: F) -> Tree<B>
+    where
+        F: FnMut(T) -> B,
+    {
+        self.traverse_with_mut_f(&mut f)
+    }
+
+    #[inline(always)]
+    fn traverse<B, Fb, F>(self, f: F) -> Fb::Of<Tree<B>>
+    where
+        Fb: Applicative<HktArg1 = B>,
+        Fb::Of<Tree<B>>: Applicative<HktArg1 = Tree<B>, Of<Tree<B>> = Fb::Of<Tree<B>>>,
+        Tree<B>: Clone,
+        F: Fn(T) -> Fb,
+    {
+        self.traverse_ref_f(&f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,149 @@
+use crate::base::hkt::Hkt1;
+use crate::impl_hkt1;
+
+use super::applicative::Applicative;
+use super::foldable::Foldable;
+use super::functor::Functor;
+use super::monad::Monad;
+use super::traversable::Traversable;
+
+impl_hkt1!(Vec);
+
+impl<A> Functor for Vec<A> {
+    type Map<B, F> = Vec<B>
+    where
+        F: Fn(A) -> B + Clone;
+
+    #[inline(always)]
+    fn fmap<B, F>(self, f: F) -> Vec<B>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        self.into_iter().map(f).collect()
+    }
+}
+
+impl<A> Applicative for Vec<A>
+where
+    A: Clone,
+{
+    type Of<T> = Vec<T>;
+
+    #[inline(always)]
+    fn pure(a: A) -> Self {
+        vec![a]
+    }
+
+    #[inline(always)]
+    fn lift_a<B, F>(self, f: F) -> Vec<B>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        self.fmap(f)
+    }
+
+    fn apply<B, F>(self, ff: Vec<F>) -> Vec<B>
+    where
+        F: Fn(A) -> B + Clone,
+    {
+        ff.into_iter()
+            .flat_map(|f| self.iter().cloned().map(f))
+            .collect()
+    }
+
+    fn lift_a2<B, C, F>(self, fb: Vec<B>, f: F) -> Vec<C>
+    where
+        B: Clone,
+        F: Fn(A, B) -> C + Clone,
+    {
+        let mut result = Vec::with_capacity(self.len() * fb.len());
+        for a in self {
+            for b in fb.iter() {
+                result.push(f(a.clone(), b.clone()));
+            }
+        }
+        result
+    }
+}
+
+impl<A> Monad for Vec<A>
+where
+    A: Clone,
+{
+    #[inline(always)]
+    fn bind<B, F>(self, f: F) -> Vec<B>
+    where
+        F: Fn(A) -> Vec<B> + Clone,
+    {
+        self.into_iter().flat_map(f).collect()
+    }
+}
+
+impl<A> Foldable for Vec<A> {
+    #[inline(always)]
+    fn foldr<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(A, B) -> B,
+    {
+        self.into_iter().rfold(init, |acc, a| f(a, acc))
+    }
+
+    #[inline(always)]
+    fn foldl<B, F>(self, f: F, init: B) -> B
+    where
+        F: Fn(B, A) -> B,
+    {
+        self.into_iter().fold(init, f)
+    }
+
+    #[inline(always)]
+    fn length(self) -> usize {
+        self.len()
+    }
+}
+
+impl<A> Traversable for Vec<A> {
+    type Of<T> = Vec<T>;
+
+    #[inline(always)]
+    fn traverse_with<B, F>(self, f: F) -> Vec<B>
+    where
+        F: FnMut(A) -> B,
+    {
+        self.into_iter().map(f).collect()
+    }
+
+    fn traverse<B, Fb, F>(self, f: F) -> Fb::Of<Vec<B>>
+    where
+        Fb: Applicative<HktArg1 = B>,
+        Fb::Of<Vec<B>>: Applicative<HktArg1 = Vec<B>, Of<Vec<B>> = Fb::Of<Vec<B>>>,
+        Vec<B>: Clone,
+        F: Fn(A) -> Fb,
+    {
+        let mut acc = <Fb::Of<Vec<B>>>::pure(Vec::with_capacity(self.len()));
+        for a in self {
+            acc = acc.lift_a2(f(a).lift_a(|b| vec![b]), |mut bs, b: Vec<B>| {
+                bs.extend(b);
+                bs
+            });
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vec_functor() {
+        assert_eq!(Vec::<i32>::new().fmap(|x| x + 1), Vec::<i32>::new());
+        assert_eq!(vec![1, 2, 3].fmap(|x| x + 1), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_vec_apply() {
+        let fs: Vec<fn(i32) -> i32> = vec![|x| x + 1, |x| x * 10];
+        assert_eq!(vec![1, 2].apply(fs), vec![2, 3, 10, 20]);
+    }
+}
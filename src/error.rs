@@ -0,0 +1,70 @@
+use std::fmt;
+
+/// Errors raised by the fallible `try_*` actions.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Utf8(std::string::FromUtf8Error),
+    /// A child process ran to completion but did not succeed.
+    ProcessFailed(std::process::ExitStatus),
+    #[cfg(feature = "http_client")]
+    Http(reqwest::Error),
+    #[cfg(feature = "websocket")]
+    WebSocket(Box<tungstenite::Error>),
+}
+
+pub type IoResult<T> = Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Utf8(e) => write!(f, "invalid utf-8: {}", e),
+            Error::ProcessFailed(status) => write!(f, "process failed: {}", status),
+            #[cfg(feature = "http_client")]
+            Error::Http(e) => write!(f, "http error: {}", e),
+            #[cfg(feature = "websocket")]
+            Error::WebSocket(e) => write!(f, "websocket error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Utf8(e) => Some(e),
+            Error::ProcessFailed(_) => None,
+            #[cfg(feature = "http_client")]
+            Error::Http(e) => Some(e),
+            #[cfg(feature = "websocket")]
+            Error::WebSocket(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        Error::Utf8(e)
+    }
+}
+
+#[cfg(feature = "http_client")]
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl From<tungstenite::Error> for Error {
+    fn from(e: tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
@@ -1,12 +1,15 @@
 use reqwest::blocking::Client;
 
-use crate::prelude::Io;
+use crate::{control::concurrent::current_thread_deadline, error::IoResult, prelude::Io};
 
 pub fn new_client() -> Client {
-    Client::builder()
+    try_new_client().unwrap()
+}
+
+pub fn try_new_client() -> IoResult<Client> {
+    Ok(Client::builder()
         .cookie_provider(std::sync::Arc::new(reqwest::cookie::Jar::default()))
-        .build()
-        .unwrap()
+        .build()?)
 }
 
 pub struct HttpRequestIo {
@@ -17,10 +20,80 @@ impl Io for HttpRequestIo {
     type Output = reqwest::blocking::Response;
 
     fn run(self) -> Self::Output {
-        self.request.send().unwrap()
+        try_http_request(self.request).run().unwrap()
     }
 }
 
 pub fn http_request(request: reqwest::blocking::RequestBuilder) -> HttpRequestIo {
     HttpRequestIo { request }
 }
+
+pub struct TryHttpRequestIo {
+    request: reqwest::blocking::RequestBuilder,
+}
+
+impl Io for TryHttpRequestIo {
+    type Output = IoResult<reqwest::blocking::Response>;
+
+    fn run(self) -> Self::Output {
+        let request = match current_thread_deadline() {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(std::time::Instant::now());
+                // Keep a shorter timeout set on the request itself
+                let own = self
+                    .request
+                    .try_clone()
+                    .and_then(|request| request.build().ok()?.timeout().copied());
+                self.request.timeout(own.map_or(left, |own| own.min(left)))
+            }
+            None => self.request,
+        };
+
+        Ok(request.send()?)
+    }
+}
+
+/// Send the request; under `timeout` it fails once the deadline passes, closing the connection
+pub fn try_http_request(request: reqwest::blocking::RequestBuilder) -> TryHttpRequestIo {
+    TryHttpRequestIo { request }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::control::concurrent::timeout;
+
+    #[test]
+    fn test_timeout_interrupts_http_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Reads the request, never answers and reports whether the client hung up
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buffer = [0; 1024];
+            loop {
+                match stream.read(&mut buffer) {
+                    Ok(0) => return true,
+                    Ok(_) => {}
+                    Err(_) => return false,
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let request = new_client().get(format!("http://{}/", addr));
+
+        assert!(timeout(Duration::from_millis(50), http_request(request))
+            .run()
+            .is_none());
+        assert!(server.join().unwrap());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
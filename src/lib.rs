@@ -2,6 +2,7 @@ pub mod base;
 pub mod data;
 
 pub mod control;
+pub mod error;
 pub mod prelude;
 pub mod system;
 
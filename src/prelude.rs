@@ -1,3 +1,5 @@
+use std::panic::{self, AssertUnwindSafe};
+
 // Tuples
 
 /// O(1)
@@ -561,6 +563,40 @@ pub trait Io: Sized {
             f: |_| mb,
         }
     }
+
+    /// Run `after` once this action finishes, whether it returns or panics.
+    fn finally<B>(self, after: B) -> IoFinally<Self, B>
+    where
+        B: Io,
+    {
+        IoFinally { io: self, after }
+    }
+
+    /// Run `handler` only if this action panics, then keep unwinding.
+    fn on_exception<B>(self, handler: B) -> IoOnException<Self, B>
+    where
+        B: Io,
+    {
+        IoOnException { io: self, handler }
+    }
+
+    /// Use this action's output as a resource, which `release` receives once `use_` is done.
+    ///
+    /// `release` runs even if `use_` panics. See the free function `bracket`.
+    fn bracket<R, F, G, B, C>(self, release: G, use_: F) -> IoBracket<Self, G, F>
+    where
+        Self: Io<Output = R>,
+        F: FnOnce(&mut R) -> C,
+        G: FnOnce(R) -> B,
+        B: Io,
+        C: Io,
+    {
+        IoBracket {
+            acquire: self,
+            release,
+            use_,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -613,6 +649,312 @@ where
     }
 }
 
+#[derive(Clone)]
+pub struct IoFinally<A, B> {
+    io: A,
+    after: B,
+}
+
+impl<A, B> Io for IoFinally<A, B>
+where
+    A: Io,
+    B: Io,
+{
+    type Output = A::Output;
+
+    fn run(self) -> Self::Output {
+        let io = self.io;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| io.run()));
+        self.after.run();
+
+        match result {
+            Ok(output) => output,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IoOnException<A, B> {
+    io: A,
+    handler: B,
+}
+
+impl<A, B> Io for IoOnException<A, B>
+where
+    A: Io,
+    B: Io,
+{
+    type Output = A::Output;
+
+    fn run(self) -> Self::Output {
+        let io = self.io;
+        match panic::catch_unwind(AssertUnwindSafe(|| io.run())) {
+            Ok(output) => output,
+            Err(payload) => {
+                self.handler.run();
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IoBracket<A, G, F> {
+    acquire: A,
+    release: G,
+    use_: F,
+}
+
+impl<A, R, F, G, B, C> Io for IoBracket<A, G, F>
+where
+    A: Io<Output = R>,
+    F: FnOnce(&mut R) -> C,
+    G: FnOnce(R) -> B,
+    B: Io,
+    C: Io,
+{
+    type Output = C::Output;
+
+    fn run(self) -> Self::Output {
+        let mut resource = self.acquire.run();
+        let use_ = self.use_;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| use_(&mut resource).run()));
+        (self.release)(resource).run();
+
+        match result {
+            Ok(output) => output,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Acquire a resource, use it, and release it even if using it panics.
+///
+/// `use_` borrows the resource and `release` then takes ownership of it, so resources which
+/// can't be cloned, like a `Child` or a `File`, work too. The action returned by `use_` runs
+/// after the borrow ends, so work needing the resource happens inside `use_` itself.
+pub fn bracket<A, R, F, G, B, C>(acquire: A, release: G, use_: F) -> IoBracket<A, G, F>
+where
+    A: Io<Output = R>,
+    F: FnOnce(&mut R) -> C,
+    G: FnOnce(R) -> B,
+    B: Io,
+    C: Io,
+{
+    IoBracket {
+        acquire,
+        release,
+        use_,
+    }
+}
+
+/// `bracket` for when neither step needs the acquired value.
+pub fn bracket_<A, B, C>(
+    acquire: A,
+    release: B,
+    use_: C,
+) -> IoBind<A, impl FnOnce(A::Output) -> IoFinally<C, B> + Clone>
+where
+    A: Io,
+    B: Io + Clone,
+    C: Io + Clone,
+{
+    acquire.and_then(move |_| use_.finally(release))
+}
+
+// Fallible Io
+
+/// An `Io` whose output is a `Result`.
+///
+/// The combinators short-circuit on the first `Err`, the same way `?` does.
+pub trait TryIo: Io {
+    type Ok;
+    type Err;
+
+    fn try_run(self) -> Result<Self::Ok, Self::Err>;
+
+    fn map_ok<B, F>(self, f: F) -> TryIoMapOk<Self, F>
+    where
+        F: FnOnce(Self::Ok) -> B,
+    {
+        TryIoMapOk { io: self, f }
+    }
+
+    fn map_err<E, F>(self, f: F) -> TryIoMapErr<Self, F>
+    where
+        F: FnOnce(Self::Err) -> E,
+    {
+        TryIoMapErr { io: self, f }
+    }
+
+    /// Fallible counterpart of `Io::and_then`.
+    /// The error of `self` is converted with `From`, as `?` would.
+    fn try_and_then<B, F>(self, f: F) -> TryIoBind<Self, F>
+    where
+        F: FnOnce(Self::Ok) -> B + Clone,
+        B: TryIo,
+        B::Err: From<Self::Err>,
+    {
+        TryIoBind { io: self, f }
+    }
+
+    fn try_then<Mb>(self, mb: Mb) -> TryIoBind<Self, impl FnOnce(Self::Ok) -> Mb + Clone>
+    where
+        Mb: TryIo + Clone,
+        Mb::Err: From<Self::Err>,
+    {
+        TryIoBind {
+            io: self,
+            f: |_| mb,
+        }
+    }
+
+    /// Recover from an error with another fallible action.
+    fn or_else<B, F>(self, f: F) -> TryIoOrElse<Self, F>
+    where
+        F: FnOnce(Self::Err) -> B,
+        B: TryIo<Ok = Self::Ok>,
+    {
+        TryIoOrElse { io: self, f }
+    }
+
+    /// Recover from an error with an infallible action.
+    fn catch<B, F>(self, f: F) -> TryIoCatch<Self, F>
+    where
+        F: FnOnce(Self::Err) -> B,
+        B: Io<Output = Self::Ok>,
+    {
+        TryIoCatch { io: self, f }
+    }
+}
+
+impl<I, T, E> TryIo for I
+where
+    I: Io<Output = Result<T, E>>,
+{
+    type Ok = T;
+    type Err = E;
+
+    #[inline(always)]
+    fn try_run(self) -> Result<T, E> {
+        self.run()
+    }
+}
+
+/// Lift a `Result` into a fallible `Io`.
+pub fn from_result<T, E>(result: Result<T, E>) -> IoPure<Result<T, E>> {
+    IoPure { io: result }
+}
+
+/// A fallible `Io` which always fails with the given error.
+pub fn throw_io<T, E>(e: E) -> IoPure<Result<T, E>> {
+    IoPure { io: Err(e) }
+}
+
+#[derive(Clone)]
+pub struct TryIoMapOk<I, F> {
+    io: I,
+    f: F,
+}
+
+impl<I, F, B> Io for TryIoMapOk<I, F>
+where
+    I: TryIo,
+    F: FnOnce(I::Ok) -> B,
+{
+    type Output = Result<B, I::Err>;
+
+    fn run(self) -> Self::Output {
+        self.io.try_run().map(self.f)
+    }
+}
+
+#[derive(Clone)]
+pub struct TryIoMapErr<I, F> {
+    io: I,
+    f: F,
+}
+
+impl<I, F, E> Io for TryIoMapErr<I, F>
+where
+    I: TryIo,
+    F: FnOnce(I::Err) -> E,
+{
+    type Output = Result<I::Ok, E>;
+
+    fn run(self) -> Self::Output {
+        self.io.try_run().map_err(self.f)
+    }
+}
+
+#[derive(Clone)]
+pub struct TryIoBind<I, F> {
+    io: I,
+    f: F,
+}
+
+impl<I, B, F> Io for TryIoBind<I, F>
+where
+    I: TryIo,
+    B: TryIo,
+    B::Err: From<I::Err>,
+    F: FnOnce(I::Ok) -> B,
+{
+    type Output = Result<B::Ok, B::Err>;
+
+    fn run(self) -> Self::Output {
+        match self.io.try_run() {
+            Ok(a) => (self.f)(a).try_run(),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TryIoOrElse<I, F> {
+    io: I,
+    f: F,
+}
+
+impl<I, B, F> Io for TryIoOrElse<I, F>
+where
+    I: TryIo,
+    B: TryIo<Ok = I::Ok>,
+    F: FnOnce(I::Err) -> B,
+{
+    type Output = Result<I::Ok, B::Err>;
+
+    fn run(self) -> Self::Output {
+        match self.io.try_run() {
+            Ok(a) => Ok(a),
+            Err(e) => (self.f)(e).try_run(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TryIoCatch<I, F> {
+    io: I,
+    f: F,
+}
+
+impl<I, B, F> Io for TryIoCatch<I, F>
+where
+    I: TryIo,
+    B: Io<Output = I::Ok>,
+    F: FnOnce(I::Err) -> B,
+{
+    type Output = I::Ok;
+
+    fn run(self) -> Self::Output {
+        match self.io.try_run() {
+            Ok(a) => a,
+            Err(e) => (self.f)(e).run(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct PutStr(std::string::String);
 
@@ -620,7 +962,7 @@ impl crate::prelude::Io for PutStr {
     type Output = ();
 
     fn run(self) {
-        print!("{}", self.0);
+        crate::system::io::h_put_str(crate::system::io::stdout(), self.0).run()
     }
 }
 
@@ -638,7 +980,7 @@ impl crate::prelude::Io for PutStrLn {
     type Output = ();
 
     fn run(self) {
-        println!("{}", self.0);
+        crate::system::io::h_put_str_ln(crate::system::io::stdout(), self.0).run()
     }
 }
 
@@ -659,19 +1001,58 @@ impl crate::prelude::Io for get_line {
     type Output = String;
 
     fn run(self) -> String {
-        let mut s = String::new();
-        std::io::stdin().read_line(&mut s).unwrap();
+        try_get_line.run().unwrap()
+    }
+}
 
-        // Check if the last character is a newline and remove it
-        if s.ends_with('\n') {
-            s.pop();
-        }
-        // Check for Windows-style newline (\r\n)
-        if s.ends_with('\r') {
-            s.pop();
-        }
+#[derive(Clone)]
+pub struct TryPutStr(std::string::String);
+
+impl crate::prelude::Io for TryPutStr {
+    type Output = crate::error::IoResult<()>;
 
-        s
+    fn run(self) -> Self::Output {
+        let stdout = crate::system::io::stdout();
+        crate::system::io::try_h_put_str(stdout.clone(), self.0).run()?;
+        crate::system::io::try_h_flush(stdout).run()
+    }
+}
+
+pub fn try_put_str<S>(s: S) -> TryPutStr
+where
+    S: Into<String>,
+{
+    TryPutStr(s.into())
+}
+
+#[derive(Clone)]
+pub struct TryPutStrLn(std::string::String);
+
+impl crate::prelude::Io for TryPutStrLn {
+    type Output = crate::error::IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        crate::system::io::try_h_put_str_ln(crate::system::io::stdout(), self.0).run()
+    }
+}
+
+pub fn try_put_str_ln<S>(s: S) -> TryPutStrLn
+where
+    S: Into<String>,
+{
+    TryPutStrLn(s.into())
+}
+
+/// Fallible version of `get_line`.
+#[allow(non_camel_case_types)]
+#[derive(Clone)]
+pub struct try_get_line;
+
+impl crate::prelude::Io for try_get_line {
+    type Output = crate::error::IoResult<String>;
+
+    fn run(self) -> Self::Output {
+        crate::system::io::try_h_get_line(crate::system::io::stdin()).run()
     }
 }
 
@@ -1192,6 +1573,126 @@ mod tests {
 
     // No break since it is a keyword
 
+    // Fallible Io
+
+    #[test]
+    fn test_try_io_map() {
+        assert_eq!(from_result::<i32, ()>(Ok(1)).map_ok(|x| x + 1).run(), Ok(2));
+        assert_eq!(throw_io::<i32, i32>(1).map_err(|e| e + 1).run(), Err(2));
+    }
+
+    #[test]
+    fn test_try_io_and_then() {
+        let ok = from_result::<i32, String>(Ok(1)).try_and_then(|x| from_result(Ok(x + 1)));
+        assert_eq!(ok.run(), Ok::<_, String>(2));
+
+        let short_circuit =
+            throw_io::<i32, &str>("boom").try_and_then(|x| from_result::<i32, String>(Ok(x + 1)));
+        assert_eq!(short_circuit.run(), Err("boom".to_string()));
+    }
+
+    #[test]
+    fn test_try_io_recover() {
+        assert_eq!(
+            throw_io::<i32, &str>("boom")
+                .or_else(|e| from_result::<i32, usize>(Err(e.len())))
+                .run(),
+            Err(4)
+        );
+        assert_eq!(
+            throw_io::<i32, &str>("boom")
+                .catch(|e| IoPure { io: e.len() as i32 })
+                .run(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_io_finally() {
+        let log = &std::cell::RefCell::new(Vec::new());
+        let push = |x| IoPure { io: () }.map(move |_| log.borrow_mut().push(x));
+
+        let output = IoPure { io: 1 }.finally(push(2)).run();
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+            IoPure { io: () }
+                .map(|_| panic!("boom"))
+                .finally(push(3))
+                .run()
+        }));
+
+        assert_eq!(output, 1);
+        assert!(unwound.is_err());
+        assert_eq!(*log.borrow(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_io_on_exception() {
+        let log = &std::cell::RefCell::new(Vec::new());
+        let push = |x| IoPure { io: () }.map(move |_| log.borrow_mut().push(x));
+
+        IoPure { io: () }.on_exception(push(1)).run();
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+            IoPure { io: () }
+                .map(|_| panic!("boom"))
+                .on_exception(push(2))
+                .run()
+        }));
+
+        assert!(unwound.is_err());
+        assert_eq!(*log.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn test_io_bracket() {
+        let released = std::rc::Rc::new(std::cell::Cell::new(0));
+
+        let output = bracket(
+            IoPure {
+                io: released.clone(),
+            },
+            |r: std::rc::Rc<std::cell::Cell<i32>>| {
+                IoPure { io: () }.map(move |_| r.set(r.get() + 1))
+            },
+            |_| IoPure { io: 42 },
+        )
+        .run();
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+            IoPure {
+                io: released.clone(),
+            }
+            .bracket(
+                |r| IoPure { io: () }.map(move |_| r.set(r.get() + 1)),
+                |_| IoPure { io: () }.map(|_| panic!("boom")),
+            )
+            .run()
+        }));
+
+        assert_eq!(output, 42);
+        assert!(unwound.is_err());
+        assert_eq!(released.get(), 2);
+    }
+
+    #[test]
+    fn test_io_bracket_without_clone() {
+        struct Resource(Vec<i32>);
+        let released = &std::cell::RefCell::new(Vec::new());
+
+        let output = bracket(
+            IoPure {
+                io: Resource(vec![1]),
+            },
+            |r: Resource| IoPure { io: () }.map(move |_| *released.borrow_mut() = r.0),
+            |r: &mut Resource| {
+                r.0.push(2);
+                IoPure { io: r.0.len() }
+            },
+        )
+        .run();
+
+        assert_eq!(output, 2);
+        assert_eq!(*released.borrow(), vec![1, 2]);
+    }
+
     // Additional functions
 
     #[test]
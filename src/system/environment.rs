@@ -0,0 +1,367 @@
+use std::{cell::RefCell, path::PathBuf};
+
+use crate::{error::IoResult, prelude::Io};
+
+thread_local! {
+    static ARGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+// Arguments
+
+#[derive(Clone)]
+pub struct GetArgsIo;
+
+impl Io for GetArgsIo {
+    type Output = Vec<String>;
+
+    fn run(self) -> Self::Output {
+        ARGS.with(|args| args.borrow().clone()).unwrap_or_else(|| {
+            std::env::args_os()
+                .skip(1)
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        })
+    }
+}
+
+/// The command line arguments, without the program name
+///
+/// Invalid UTF-8 is replaced with U+FFFD. Inside `with_args` these are the arguments given there.
+pub fn get_args() -> GetArgsIo {
+    GetArgsIo
+}
+
+#[derive(Clone)]
+pub struct GetProgNameIo;
+
+impl Io for GetProgNameIo {
+    type Output = String;
+
+    fn run(self) -> Self::Output {
+        let arg0 = std::env::args_os().next().unwrap_or_default();
+
+        PathBuf::from(arg0)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// The name the program was invoked with, without its directory
+pub fn get_prog_name() -> GetProgNameIo {
+    GetProgNameIo
+}
+
+struct RestoreArgs(Option<Vec<String>>);
+
+impl Drop for RestoreArgs {
+    fn drop(&mut self) {
+        ARGS.with(|args| *args.borrow_mut() = self.0.take());
+    }
+}
+
+pub struct WithArgsIo<I> {
+    args: Vec<String>,
+    io: I,
+}
+
+impl<I> Io for WithArgsIo<I>
+where
+    I: Io,
+{
+    type Output = I::Output;
+
+    fn run(self) -> Self::Output {
+        let previous = ARGS.with(|args| args.replace(Some(self.args)));
+        let _restore = RestoreArgs(previous);
+
+        self.io.run()
+    }
+}
+
+/// Run `io` with `get_args` returning `args` on this thread
+///
+/// The previous arguments are restored afterwards, even if `io` panics.
+pub fn with_args<I: Io>(args: Vec<String>, io: I) -> WithArgsIo<I> {
+    WithArgsIo { args, io }
+}
+
+#[derive(Clone)]
+pub struct GetExecutablePathIo;
+
+impl Io for GetExecutablePathIo {
+    type Output = PathBuf;
+
+    fn run(self) -> Self::Output {
+        try_get_executable_path().run().unwrap()
+    }
+}
+
+pub fn get_executable_path() -> GetExecutablePathIo {
+    GetExecutablePathIo
+}
+
+#[derive(Clone)]
+pub struct TryGetExecutablePathIo;
+
+impl Io for TryGetExecutablePathIo {
+    type Output = IoResult<PathBuf>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::env::current_exe()?)
+    }
+}
+
+/// The absolute path of the running executable
+pub fn try_get_executable_path() -> TryGetExecutablePathIo {
+    TryGetExecutablePathIo
+}
+
+// Environment variables
+
+#[derive(Clone)]
+pub struct LookupEnvIo {
+    name: String,
+}
+
+impl Io for LookupEnvIo {
+    type Output = Option<String>;
+
+    fn run(self) -> Self::Output {
+        std::env::var_os(self.name).map(|value| value.to_string_lossy().into_owned())
+    }
+}
+
+/// The value of an environment variable, if it is set
+pub fn lookup_env(name: String) -> LookupEnvIo {
+    LookupEnvIo { name }
+}
+
+#[derive(Clone)]
+pub struct GetEnvironmentIo;
+
+impl Io for GetEnvironmentIo {
+    type Output = Vec<(String, String)>;
+
+    fn run(self) -> Self::Output {
+        std::env::vars_os()
+            .map(|(name, value)| {
+                (
+                    name.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Every environment variable of the process
+pub fn get_environment() -> GetEnvironmentIo {
+    GetEnvironmentIo
+}
+
+fn check_env_name(name: &str) -> IoResult<()> {
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid environment variable name: {:?}", name),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct SetEnvIo {
+    name: String,
+    value: String,
+}
+
+impl Io for SetEnvIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_set_env(self.name, self.value).run().unwrap()
+    }
+}
+
+/// See `try_set_env`, including its warning about other threads
+pub fn set_env(name: String, value: String) -> SetEnvIo {
+    SetEnvIo { name, value }
+}
+
+#[derive(Clone)]
+pub struct TrySetEnvIo {
+    name: String,
+    value: String,
+}
+
+impl Io for TrySetEnvIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        check_env_name(&self.name)?;
+        if self.value.contains('\0') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "environment variable value contains a NUL byte",
+            )
+            .into());
+        }
+
+        std::env::set_var(self.name, self.value);
+        Ok(())
+    }
+}
+
+/// Set an environment variable for the whole process
+///
+/// This races with any other thread reading the environment. Rust's own accesses are serialized,
+/// but C code calling `getenv`, such as name resolution, is not, which is undefined behaviour on
+/// most Unix platforms. Only change the environment while no other thread might read it, for
+/// example before `fork_io` or `concurrently` start any.
+pub fn try_set_env(name: String, value: String) -> TrySetEnvIo {
+    TrySetEnvIo { name, value }
+}
+
+#[derive(Clone)]
+pub struct UnsetEnvIo {
+    name: String,
+}
+
+impl Io for UnsetEnvIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_unset_env(self.name).run().unwrap()
+    }
+}
+
+/// See `try_unset_env`, including its warning about other threads
+pub fn unset_env(name: String) -> UnsetEnvIo {
+    UnsetEnvIo { name }
+}
+
+#[derive(Clone)]
+pub struct TryUnsetEnvIo {
+    name: String,
+}
+
+impl Io for TryUnsetEnvIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        check_env_name(&self.name)?;
+
+        std::env::remove_var(self.name);
+        Ok(())
+    }
+}
+
+/// Remove an environment variable from the process, doing nothing if it isn't set
+///
+/// Like `try_set_env`, this must not run while another thread might read the environment.
+pub fn try_unset_env(name: String) -> TryUnsetEnvIo {
+    TryUnsetEnvIo { name }
+}
+
+struct RestoreEnv {
+    name: String,
+    previous: Option<std::ffi::OsString>,
+}
+
+impl Drop for RestoreEnv {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(value) => std::env::set_var(&self.name, value),
+            None => std::env::remove_var(&self.name),
+        }
+    }
+}
+
+pub struct WithEnvIo<I> {
+    name: String,
+    value: Option<String>,
+    io: I,
+}
+
+impl<I> Io for WithEnvIo<I>
+where
+    I: Io,
+{
+    type Output = I::Output;
+
+    fn run(self) -> Self::Output {
+        let previous = std::env::var_os(&self.name);
+
+        match self.value {
+            Some(value) => set_env(self.name.clone(), value).run(),
+            None => unset_env(self.name.clone()).run(),
+        }
+
+        // Only restore once the name is known to be valid
+        let _restore = RestoreEnv {
+            name: self.name,
+            previous,
+        };
+
+        self.io.run()
+    }
+}
+
+/// Run `io` with an environment variable set, or unset for `None`
+///
+/// The previous value is restored afterwards, even if `io` panics. The environment is shared by
+/// the whole process, so other threads see the change while `io` runs, and setting and restoring
+/// it has the same hazard as `try_set_env`: no other thread may read the environment meanwhile.
+pub fn with_env<I: Io>(name: String, value: Option<String>, io: I) -> WithEnvIo<I> {
+    WithEnvIo { name, value, io }
+}
+
+/// Held by tests which change or depend on the process environment
+#[cfg(test)]
+pub(crate) static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_args() {
+        let args = with_args(vec!["a".into(), "b".into()], get_args()).run();
+
+        assert_eq!(args, vec!["a", "b"]);
+        assert_eq!(
+            get_args().run(),
+            std::env::args_os()
+                .skip(1)
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_with_env_restores() {
+        let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let name = "ENTOLI_TEST_WITH_ENV".to_string();
+        set_env(name.clone(), "outer".into()).run();
+
+        let inner = with_env(name.clone(), Some("inner".into()), lookup_env(name.clone())).run();
+        let unset = with_env(name.clone(), None, lookup_env(name.clone())).run();
+
+        assert_eq!(inner.as_deref(), Some("inner"));
+        assert_eq!(unset, None);
+        assert_eq!(lookup_env(name.clone()).run().as_deref(), Some("outer"));
+
+        unset_env(name.clone()).run();
+        with_env(name.clone(), Some("x".into()), get_environment()).run();
+        assert_eq!(lookup_env(name).run(), None);
+    }
+
+    #[test]
+    fn test_set_env_rejects_invalid_names() {
+        assert!(try_set_env("A=B".into(), "c".into()).run().is_err());
+        assert!(try_unset_env(String::new()).run().is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::{Mutex, Once},
+};
+
+use super::process::ExitCode;
+use crate::prelude::Io;
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+static INSTALL: Once = Once::new();
+
+/// Run and forget every registered hook, most recently registered first
+///
+/// A panicking hook doesn't stop the others.
+fn run_exit_hooks() {
+    loop {
+        // Popped one at a time so hooks may register more hooks
+        let hook = HOOKS.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        match hook {
+            Some(hook) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(hook));
+            }
+            None => break,
+        }
+    }
+}
+
+// Exiting
+
+pub struct ExitWithIo<T> {
+    code: ExitCode,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ExitWithIo<T> {
+    fn clone(&self) -> Self {
+        exit_with(self.code)
+    }
+}
+
+impl<T> Io for ExitWithIo<T> {
+    type Output = T;
+
+    fn run(self) -> Self::Output {
+        run_exit_hooks();
+        std::process::exit(i32::from(self.code.status_byte()))
+    }
+}
+
+/// Run the exit hooks and end the program with `code`
+///
+/// A `Signaled` code exits with `128 + signal`, as shells report it, and a failure which would
+/// truncate to 0 exits with 1. Like Haskell's `exitWith :: IO a` it never returns, so its output
+/// can be any type; annotate it, as in `exit_success::<()>()`, where nothing else fixes it.
+pub fn exit_with<T>(code: ExitCode) -> ExitWithIo<T> {
+    ExitWithIo {
+        code,
+        _t: PhantomData,
+    }
+}
+
+pub fn exit_success<T>() -> ExitWithIo<T> {
+    exit_with(ExitCode::Success)
+}
+
+pub fn exit_failure<T>() -> ExitWithIo<T> {
+    exit_with(ExitCode::Failure(1))
+}
+
+pub struct DieIo<T> {
+    message: String,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for DieIo<T> {
+    fn clone(&self) -> Self {
+        die(self.message.clone())
+    }
+}
+
+impl<T> Io for DieIo<T> {
+    type Output = T;
+
+    fn run(self) -> Self::Output {
+        eprintln!("{}", self.message);
+        exit_failure().run()
+    }
+}
+
+/// Print `message` to stderr and exit with a failure
+pub fn die<T>(message: String) -> DieIo<T> {
+    DieIo {
+        message,
+        _t: PhantomData,
+    }
+}
+
+// Hooks
+
+#[cfg(unix)]
+extern "C" fn run_exit_hooks_at_exit() {
+    run_exit_hooks();
+}
+
+#[cfg(unix)]
+static SIGNAL_PIPE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(-1);
+
+/// The actions replaced by `install`, put back before a caught signal is re-raised
+#[cfg(unix)]
+static PREVIOUS_ACTIONS: Mutex<Vec<(libc::c_int, libc::sigaction)>> = Mutex::new(Vec::new());
+
+#[cfg(unix)]
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let fd = SIGNAL_PIPE.load(std::sync::atomic::Ordering::Relaxed);
+    let byte = signal as u8;
+
+    // write is async-signal-safe; the watcher thread does the rest
+    unsafe { libc::write(fd, &byte as *const u8 as *const libc::c_void, 1) };
+}
+
+/// Run the hooks on normal exit through `atexit`, and on SIGINT and SIGTERM from a watcher thread
+/// which then re-raises the signal with the action it had before, default or not.
+///
+/// A signal which was ignored is left ignored.
+#[cfg(unix)]
+fn install() {
+    unsafe { libc::atexit(run_exit_hooks_at_exit) };
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return;
+    }
+    let [read_fd, write_fd] = fds;
+    for fd in fds {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    SIGNAL_PIPE.store(write_fd, std::sync::atomic::Ordering::Relaxed);
+
+    std::thread::spawn(move || loop {
+        let mut byte = 0u8;
+        let read = unsafe { libc::read(read_fd, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if read == 1 {
+            let signal = libc::c_int::from(byte);
+            run_exit_hooks();
+
+            let previous = PREVIOUS_ACTIONS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .find(|(caught, _)| *caught == signal)
+                .map(|(_, action)| *action);
+            unsafe {
+                match previous {
+                    Some(action) => libc::sigaction(signal, &action, std::ptr::null_mut()),
+                    None => {
+                        libc::signal(signal, libc::SIG_DFL);
+                        0
+                    }
+                };
+                libc::raise(signal);
+            }
+        } else if read == 0
+            || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+        {
+            return;
+        }
+    });
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        unsafe {
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(signal, std::ptr::null(), &mut previous);
+            // Ignored signals, say under nohup, should stay that way
+            if previous.sa_sigaction == libc::SIG_IGN {
+                continue;
+            }
+            PREVIOUS_ACTIONS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push((signal, previous));
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction =
+                forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn install() {}
+
+pub struct AtExitIo<I> {
+    io: I,
+}
+
+impl<I> Io for AtExitIo<I>
+where
+    I: Io + Send + 'static,
+{
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        INSTALL.call_once(install);
+
+        let io = self.io;
+        HOOKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Box::new(move || {
+                io.run();
+            }));
+    }
+}
+
+/// Register `io` to run when the program exits
+///
+/// Hooks run in reverse order of registration, once, on `exit_with` and its variants. On Unix they
+/// also run when `main` returns or `std::process::exit` is called, and on SIGINT or SIGTERM, after
+/// which the signal gets the handler it had before the first `at_exit`, which by default
+/// terminates the program. Ignored signals are left alone. A hook must not exit the program itself.
+pub fn at_exit<I>(io: I) -> AtExitIo<I>
+where
+    I: Io + Send + 'static,
+{
+    AtExitIo { io }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_exit_hooks_run_once_in_reverse() {
+        // Registered directly, as `at_exit` would install real atexit and signal handlers
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        for i in 0..3 {
+            let log = log.clone();
+            HOOKS.lock().unwrap().push(Box::new(move || {
+                log.lock().unwrap().push(i);
+                assert_ne!(i, 1, "a panicking hook doesn't stop the others");
+            }));
+        }
+        run_exit_hooks();
+        run_exit_hooks();
+
+        assert_eq!(*log.lock().unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_exit_output_is_any_type() {
+        fn output_of<I: Io<Output = String>>(_: &I) {}
+
+        output_of(&exit_failure());
+        output_of(&exit_with(ExitCode::Failure(2)).clone());
+        output_of(&die("bad input".to_string()));
+    }
+}
@@ -1,6 +1,12 @@
-use std::{fs::OpenOptions, io::Write, os::unix::fs::PermissionsExt, path::PathBuf};
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
 
-use crate::prelude::Io;
+use crate::{error::IoResult, prelude::Io};
 
 #[derive(Clone)]
 pub struct FileExistsIo {
@@ -45,11 +51,7 @@ impl Io for ListDirIo {
     type Output = Vec<PathBuf>;
 
     fn run(self) -> Self::Output {
-        self.path
-            .read_dir()
-            .unwrap()
-            .map(|entry| entry.unwrap().path())
-            .collect()
+        try_list_dir(self.path).run().unwrap()
     }
 }
 
@@ -57,6 +59,26 @@ pub fn list_dir(path: PathBuf) -> ListDirIo {
     ListDirIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryListDirIo {
+    path: PathBuf,
+}
+
+impl Io for TryListDirIo {
+    type Output = IoResult<Vec<PathBuf>>;
+
+    fn run(self) -> Self::Output {
+        self.path
+            .read_dir()?
+            .map(|entry| Ok(entry?.path()))
+            .collect()
+    }
+}
+
+pub fn try_list_dir(path: PathBuf) -> TryListDirIo {
+    TryListDirIo { path }
+}
+
 #[derive(Clone)]
 pub struct ReadFileIo {
     path: PathBuf,
@@ -66,7 +88,7 @@ impl Io for ReadFileIo {
     type Output = String;
 
     fn run(self) -> Self::Output {
-        std::fs::read_to_string(&self.path).unwrap()
+        try_read_file(self.path).run().unwrap()
     }
 }
 
@@ -74,6 +96,23 @@ pub fn read_file(path: PathBuf) -> ReadFileIo {
     ReadFileIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryReadFileIo {
+    path: PathBuf,
+}
+
+impl Io for TryReadFileIo {
+    type Output = IoResult<String>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::read_to_string(&self.path)?)
+    }
+}
+
+pub fn try_read_file(path: PathBuf) -> TryReadFileIo {
+    TryReadFileIo { path }
+}
+
 #[derive(Clone)]
 pub struct WriteFileIo {
     path: PathBuf,
@@ -84,7 +123,7 @@ impl Io for WriteFileIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::fs::write(&self.path, self.content).unwrap()
+        try_write_file(self.path, self.content).run().unwrap()
     }
 }
 
@@ -92,6 +131,24 @@ pub fn write_file(path: PathBuf, content: String) -> WriteFileIo {
     WriteFileIo { path, content }
 }
 
+#[derive(Clone)]
+pub struct TryWriteFileIo {
+    path: PathBuf,
+    content: String,
+}
+
+impl Io for TryWriteFileIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::write(&self.path, self.content)?)
+    }
+}
+
+pub fn try_write_file(path: PathBuf, content: String) -> TryWriteFileIo {
+    TryWriteFileIo { path, content }
+}
+
 #[derive(Clone)]
 pub struct AppendFileIo {
     path: PathBuf,
@@ -101,18 +158,105 @@ pub struct AppendFileIo {
 impl Io for AppendFileIo {
     type Output = ();
 
+    fn run(self) -> Self::Output {
+        try_append_file(self.path, self.content).run().unwrap()
+    }
+}
+
+pub fn append_file(path: PathBuf, content: String) -> AppendFileIo {
+    AppendFileIo { path, content }
+}
+
+#[derive(Clone)]
+pub struct TryAppendFileIo {
+    path: PathBuf,
+    content: String,
+}
+
+impl Io for TryAppendFileIo {
+    type Output = IoResult<()>;
+
     fn run(self) -> Self::Output {
         let mut file = OpenOptions::new()
             .append(true)
             .create(true)
-            .open(&self.path)
-            .unwrap();
-        file.write_all(self.content.as_bytes()).unwrap();
+            .open(&self.path)?;
+        Ok(file.write_all(self.content.as_bytes())?)
     }
 }
 
-pub fn append_file(path: PathBuf, content: String) -> AppendFileIo {
-    AppendFileIo { path, content }
+pub fn try_append_file(path: PathBuf, content: String) -> TryAppendFileIo {
+    TryAppendFileIo { path, content }
+}
+
+#[derive(Clone)]
+pub struct ReadBytesIo {
+    path: PathBuf,
+}
+
+impl Io for ReadBytesIo {
+    type Output = Vec<u8>;
+
+    fn run(self) -> Self::Output {
+        try_read_bytes(self.path).run().unwrap()
+    }
+}
+
+pub fn read_bytes(path: PathBuf) -> ReadBytesIo {
+    ReadBytesIo { path }
+}
+
+#[derive(Clone)]
+pub struct TryReadBytesIo {
+    path: PathBuf,
+}
+
+impl Io for TryReadBytesIo {
+    type Output = IoResult<Vec<u8>>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::read(&self.path)?)
+    }
+}
+
+pub fn try_read_bytes(path: PathBuf) -> TryReadBytesIo {
+    TryReadBytesIo { path }
+}
+
+#[derive(Clone)]
+pub struct WriteBytesIo {
+    path: PathBuf,
+    content: Vec<u8>,
+}
+
+impl Io for WriteBytesIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_write_bytes(self.path, self.content).run().unwrap()
+    }
+}
+
+pub fn write_bytes(path: PathBuf, content: Vec<u8>) -> WriteBytesIo {
+    WriteBytesIo { path, content }
+}
+
+#[derive(Clone)]
+pub struct TryWriteBytesIo {
+    path: PathBuf,
+    content: Vec<u8>,
+}
+
+impl Io for TryWriteBytesIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::write(&self.path, self.content)?)
+    }
+}
+
+pub fn try_write_bytes(path: PathBuf, content: Vec<u8>) -> TryWriteBytesIo {
+    TryWriteBytesIo { path, content }
 }
 
 #[derive(Clone)]
@@ -124,7 +268,7 @@ impl Io for CreateDirIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::fs::create_dir(&self.path).unwrap()
+        try_create_dir(self.path).run().unwrap()
     }
 }
 
@@ -132,6 +276,23 @@ pub fn create_dir(path: PathBuf) -> CreateDirIo {
     CreateDirIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryCreateDirIo {
+    path: PathBuf,
+}
+
+impl Io for TryCreateDirIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::create_dir(&self.path)?)
+    }
+}
+
+pub fn try_create_dir(path: PathBuf) -> TryCreateDirIo {
+    TryCreateDirIo { path }
+}
+
 #[derive(Clone)]
 pub struct CreateDirIfMissingIo {
     parent_as_well: bool,
@@ -141,17 +302,41 @@ pub struct CreateDirIfMissingIo {
 impl Io for CreateDirIfMissingIo {
     type Output = ();
 
+    fn run(self) -> Self::Output {
+        try_create_dir_if_missing(self.parent_as_well, self.path)
+            .run()
+            .unwrap()
+    }
+}
+
+pub fn create_dir_if_missing(parent_as_well: bool, path: PathBuf) -> CreateDirIfMissingIo {
+    CreateDirIfMissingIo {
+        parent_as_well,
+        path,
+    }
+}
+
+#[derive(Clone)]
+pub struct TryCreateDirIfMissingIo {
+    parent_as_well: bool,
+    path: PathBuf,
+}
+
+impl Io for TryCreateDirIfMissingIo {
+    type Output = IoResult<()>;
+
     fn run(self) -> Self::Output {
         if self.parent_as_well {
-            std::fs::create_dir_all(&self.path).unwrap();
+            std::fs::create_dir_all(&self.path)?;
         } else {
-            std::fs::create_dir(&self.path).unwrap();
+            std::fs::create_dir(&self.path)?;
         }
+        Ok(())
     }
 }
 
-pub fn create_dir_if_missing(parent_as_well: bool, path: PathBuf) -> CreateDirIfMissingIo {
-    CreateDirIfMissingIo {
+pub fn try_create_dir_if_missing(parent_as_well: bool, path: PathBuf) -> TryCreateDirIfMissingIo {
+    TryCreateDirIfMissingIo {
         parent_as_well,
         path,
     }
@@ -166,7 +351,7 @@ impl Io for RemoveFileIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::fs::remove_file(&self.path).unwrap()
+        try_remove_file(self.path).run().unwrap()
     }
 }
 
@@ -174,6 +359,23 @@ pub fn remove_file(path: PathBuf) -> RemoveFileIo {
     RemoveFileIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryRemoveFileIo {
+    path: PathBuf,
+}
+
+impl Io for TryRemoveFileIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::remove_file(&self.path)?)
+    }
+}
+
+pub fn try_remove_file(path: PathBuf) -> TryRemoveFileIo {
+    TryRemoveFileIo { path }
+}
+
 #[derive(Clone)]
 pub struct RemoveDirIo {
     path: PathBuf,
@@ -183,7 +385,7 @@ impl Io for RemoveDirIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::fs::remove_dir(&self.path).unwrap()
+        try_remove_dir(self.path).run().unwrap()
     }
 }
 
@@ -191,6 +393,23 @@ pub fn remove_dir(path: PathBuf) -> RemoveDirIo {
     RemoveDirIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryRemoveDirIo {
+    path: PathBuf,
+}
+
+impl Io for TryRemoveDirIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::remove_dir(&self.path)?)
+    }
+}
+
+pub fn try_remove_dir(path: PathBuf) -> TryRemoveDirIo {
+    TryRemoveDirIo { path }
+}
+
 #[derive(Clone)]
 pub struct RemoveDirRecIo {
     path: PathBuf,
@@ -200,7 +419,7 @@ impl Io for RemoveDirRecIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::fs::remove_dir_all(&self.path).unwrap()
+        try_remove_dir_rec(self.path).run().unwrap()
     }
 }
 
@@ -208,6 +427,23 @@ pub fn remove_dir_rec(path: PathBuf) -> RemoveDirRecIo {
     RemoveDirRecIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryRemoveDirRecIo {
+    path: PathBuf,
+}
+
+impl Io for TryRemoveDirRecIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::remove_dir_all(&self.path)?)
+    }
+}
+
+pub fn try_remove_dir_rec(path: PathBuf) -> TryRemoveDirRecIo {
+    TryRemoveDirRecIo { path }
+}
+
 #[derive(Clone)]
 pub struct GetPermissionsIo {
     path: PathBuf,
@@ -217,7 +453,7 @@ impl Io for GetPermissionsIo {
     type Output = std::fs::Metadata;
 
     fn run(self) -> Self::Output {
-        self.path.metadata().unwrap()
+        try_get_permissions(self.path).run().unwrap()
     }
 }
 
@@ -225,6 +461,23 @@ pub fn get_permissions(path: PathBuf) -> GetPermissionsIo {
     GetPermissionsIo { path }
 }
 
+#[derive(Clone)]
+pub struct TryGetPermissionsIo {
+    path: PathBuf,
+}
+
+impl Io for TryGetPermissionsIo {
+    type Output = IoResult<std::fs::Metadata>;
+
+    fn run(self) -> Self::Output {
+        Ok(self.path.metadata()?)
+    }
+}
+
+pub fn try_get_permissions(path: PathBuf) -> TryGetPermissionsIo {
+    TryGetPermissionsIo { path }
+}
+
 #[derive(Clone)]
 pub struct SetPermissionsIo {
     path: PathBuf,
@@ -235,7 +488,7 @@ impl Io for SetPermissionsIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(self.mode)).unwrap()
+        try_set_permissions(self.path, self.mode).run().unwrap()
     }
 }
 
@@ -243,6 +496,27 @@ pub fn set_permissions(path: PathBuf, mode: u32) -> SetPermissionsIo {
     SetPermissionsIo { path, mode }
 }
 
+#[derive(Clone)]
+pub struct TrySetPermissionsIo {
+    path: PathBuf,
+    mode: u32,
+}
+
+impl Io for TrySetPermissionsIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::fs::set_permissions(
+            &self.path,
+            std::fs::Permissions::from_mode(self.mode),
+        )?)
+    }
+}
+
+pub fn try_set_permissions(path: PathBuf, mode: u32) -> TrySetPermissionsIo {
+    TrySetPermissionsIo { path, mode }
+}
+
 #[derive(Clone)]
 pub struct GetModificationTimeIo {
     path: PathBuf,
@@ -252,10 +526,969 @@ impl Io for GetModificationTimeIo {
     type Output = std::time::SystemTime;
 
     fn run(self) -> Self::Output {
-        self.path.metadata().unwrap().modified().unwrap()
+        try_get_modification_time(self.path).run().unwrap()
     }
 }
 
 pub fn get_modification_time(path: PathBuf) -> GetModificationTimeIo {
     GetModificationTimeIo { path }
 }
+
+#[derive(Clone)]
+pub struct TryGetModificationTimeIo {
+    path: PathBuf,
+}
+
+impl Io for TryGetModificationTimeIo {
+    type Output = IoResult<std::time::SystemTime>;
+
+    fn run(self) -> Self::Output {
+        Ok(self.path.metadata()?.modified()?)
+    }
+}
+
+pub fn try_get_modification_time(path: PathBuf) -> TryGetModificationTimeIo {
+    TryGetModificationTimeIo { path }
+}
+
+// Handles
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoMode {
+    Read,
+    /// Create the file or truncate it
+    Write,
+    /// Create the file or write at its end
+    Append,
+    /// Create the file if missing, keeping its contents
+    ReadWrite,
+}
+
+impl IoMode {
+    fn is_readable(self) -> bool {
+        matches!(self, IoMode::Read | IoMode::ReadWrite)
+    }
+
+    fn is_writable(self) -> bool {
+        !matches!(self, IoMode::Read)
+    }
+
+    fn open(self, path: &Path) -> std::io::Result<std::fs::File> {
+        let mut options = OpenOptions::new();
+        match self {
+            IoMode::Read => options.read(true),
+            IoMode::Write => options.write(true).create(true).truncate(true),
+            IoMode::Append => options.append(true).create(true),
+            IoMode::ReadWrite => options.read(true).write(true).create(true),
+        };
+        options.open(path)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeekMode {
+    Absolute,
+    Relative,
+    FromEnd,
+}
+
+const WRITE_BUFFER_SIZE: usize = 8 * 1024;
+
+/// A file read through one buffer and written through another
+///
+/// Each buffer is emptied before the other is used, so reads, writes and seeks see the file in
+/// the order they were made.
+struct FileHandle {
+    mode: IoMode,
+    reader: BufReader<std::fs::File>,
+    pending: Vec<u8>,
+}
+
+impl FileHandle {
+    fn flush_pending(&mut self) -> std::io::Result<()> {
+        if !self.pending.is_empty() {
+            self.reader.get_mut().write_all(&self.pending)?;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+
+    fn reader(&mut self) -> std::io::Result<&mut BufReader<std::fs::File>> {
+        if !self.mode.is_readable() {
+            return Err(handle_error("handle is not readable"));
+        }
+
+        self.flush_pending()?;
+        Ok(&mut self.reader)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if !self.mode.is_writable() {
+            return Err(handle_error("handle is not writable"));
+        }
+
+        // Drop read-ahead so the write lands where the reader stopped
+        if !self.reader.buffer().is_empty() {
+            let position = self.reader.stream_position()?;
+            self.reader.seek(SeekFrom::Start(position))?;
+        }
+
+        self.pending.extend_from_slice(bytes);
+        if self.pending.len() >= WRITE_BUFFER_SIZE {
+            self.flush_pending()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        let _ = self.flush_pending();
+    }
+}
+
+enum HandleInner {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(FileHandle),
+    Closed,
+}
+
+fn handle_error(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, message)
+}
+
+impl HandleInner {
+    fn read_line(&mut self, line: &mut String) -> std::io::Result<usize> {
+        match self {
+            HandleInner::Stdin => std::io::stdin().read_line(line),
+            HandleInner::File(file) => file.reader()?.read_line(line),
+            HandleInner::Stdout | HandleInner::Stderr => {
+                Err(handle_error("handle is not readable"))
+            }
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        match self {
+            HandleInner::Stdin => std::io::stdin().lock().read_until(byte, buf),
+            HandleInner::File(file) => file.reader()?.read_until(byte, buf),
+            HandleInner::Stdout | HandleInner::Stderr => {
+                Err(handle_error("handle is not readable"))
+            }
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            HandleInner::Stdin => std::io::stdin().read(buf),
+            HandleInner::File(file) => file.reader()?.read(buf),
+            HandleInner::Stdout | HandleInner::Stderr => {
+                Err(handle_error("handle is not readable"))
+            }
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        match self {
+            HandleInner::Stdin => std::io::stdin().read_to_end(buf),
+            HandleInner::File(file) => file.reader()?.read_to_end(buf),
+            HandleInner::Stdout | HandleInner::Stderr => {
+                Err(handle_error("handle is not readable"))
+            }
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn is_eof(&mut self) -> std::io::Result<bool> {
+        match self {
+            HandleInner::Stdin => Ok(std::io::stdin().lock().fill_buf()?.is_empty()),
+            HandleInner::File(file) => Ok(file.reader()?.fill_buf()?.is_empty()),
+            HandleInner::Stdout | HandleInner::Stderr => {
+                Err(handle_error("handle is not readable"))
+            }
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn write_str(&mut self, text: &str) -> std::io::Result<()> {
+        match self {
+            // Through the macros, so test harnesses still capture the output
+            HandleInner::Stdout => {
+                print!("{text}");
+                Ok(())
+            }
+            HandleInner::Stderr => {
+                eprint!("{text}");
+                Ok(())
+            }
+            HandleInner::File(file) => file.write(text.as_bytes()),
+            HandleInner::Stdin => Err(handle_error("handle is not writable")),
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            HandleInner::Stdout => std::io::stdout().flush(),
+            HandleInner::Stderr => std::io::stderr().flush(),
+            HandleInner::File(file) => {
+                file.flush_pending()?;
+                file.reader.get_mut().flush()
+            }
+            HandleInner::Stdin => Ok(()),
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+        }
+    }
+
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            HandleInner::File(file) => {
+                file.flush_pending()?;
+                file.reader.seek(position)
+            }
+            HandleInner::Closed => Err(handle_error("handle is closed")),
+            _ => Err(handle_error("handle is not seekable")),
+        }
+    }
+
+    fn close(&mut self) -> std::io::Result<()> {
+        if let HandleInner::Closed = self {
+            return Ok(());
+        }
+
+        let flushed = self.flush();
+        *self = HandleInner::Closed;
+        flushed
+    }
+}
+
+/// A shared reference to an open file or standard stream, as Haskell's `Handle`
+///
+/// Clones refer to the same stream and buffers. Files are flushed when closed or when the last
+/// clone is dropped.
+#[derive(Clone)]
+pub struct Handle {
+    inner: Arc<Mutex<HandleInner>>,
+}
+
+impl Handle {
+    fn new(inner: HandleInner) -> Self {
+        Handle {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut HandleInner) -> std::io::Result<T>) -> IoResult<T> {
+        Ok(f(&mut self.inner.lock().unwrap())?)
+    }
+}
+
+/// The process-wide standard input handle; closing it closes it for every caller
+pub fn stdin() -> Handle {
+    static STDIN: OnceLock<Handle> = OnceLock::new();
+    STDIN
+        .get_or_init(|| Handle::new(HandleInner::Stdin))
+        .clone()
+}
+
+/// The process-wide standard output handle; closing it closes it for every caller
+pub fn stdout() -> Handle {
+    static STDOUT: OnceLock<Handle> = OnceLock::new();
+    STDOUT
+        .get_or_init(|| Handle::new(HandleInner::Stdout))
+        .clone()
+}
+
+/// The process-wide standard error handle; closing it closes it for every caller
+pub fn stderr() -> Handle {
+    static STDERR: OnceLock<Handle> = OnceLock::new();
+    STDERR
+        .get_or_init(|| Handle::new(HandleInner::Stderr))
+        .clone()
+}
+
+#[derive(Clone)]
+pub struct OpenFileIo {
+    path: PathBuf,
+    mode: IoMode,
+}
+
+impl Io for OpenFileIo {
+    type Output = Handle;
+
+    fn run(self) -> Self::Output {
+        try_open_file(self.path, self.mode).run().unwrap()
+    }
+}
+
+pub fn open_file(path: PathBuf, mode: IoMode) -> OpenFileIo {
+    OpenFileIo { path, mode }
+}
+
+#[derive(Clone)]
+pub struct TryOpenFileIo {
+    path: PathBuf,
+    mode: IoMode,
+}
+
+impl Io for TryOpenFileIo {
+    type Output = IoResult<Handle>;
+
+    fn run(self) -> Self::Output {
+        let file = self.mode.open(&self.path)?;
+
+        Ok(Handle::new(HandleInner::File(FileHandle {
+            mode: self.mode,
+            reader: BufReader::new(file),
+            pending: Vec::new(),
+        })))
+    }
+}
+
+/// Open a file, which stays open until `h_close` or until every clone of the handle is dropped
+pub fn try_open_file(path: PathBuf, mode: IoMode) -> TryOpenFileIo {
+    TryOpenFileIo { path, mode }
+}
+
+#[derive(Clone)]
+pub struct HCloseIo {
+    handle: Handle,
+}
+
+impl Io for HCloseIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_h_close(self.handle).run().unwrap()
+    }
+}
+
+pub fn h_close(handle: Handle) -> HCloseIo {
+    HCloseIo { handle }
+}
+
+#[derive(Clone)]
+pub struct TryHCloseIo {
+    handle: Handle,
+}
+
+impl Io for TryHCloseIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        self.handle.with(HandleInner::close)
+    }
+}
+
+/// Flush and close the handle for every clone; closing it again does nothing
+pub fn try_h_close(handle: Handle) -> TryHCloseIo {
+    TryHCloseIo { handle }
+}
+
+struct CloseOnDrop(Handle);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        let _ = try_h_close(self.0.clone()).run();
+    }
+}
+
+pub struct WithFileIo<F> {
+    path: PathBuf,
+    mode: IoMode,
+    f: F,
+}
+
+impl<F, I> Io for WithFileIo<F>
+where
+    F: FnOnce(Handle) -> I,
+    I: Io,
+{
+    type Output = I::Output;
+
+    fn run(self) -> Self::Output {
+        try_with_file(self.path, self.mode, self.f).run().unwrap()
+    }
+}
+
+pub fn with_file<F, I>(path: PathBuf, mode: IoMode, f: F) -> WithFileIo<F>
+where
+    F: FnOnce(Handle) -> I,
+    I: Io,
+{
+    WithFileIo { path, mode, f }
+}
+
+pub struct TryWithFileIo<F> {
+    path: PathBuf,
+    mode: IoMode,
+    f: F,
+}
+
+impl<F, I> Io for TryWithFileIo<F>
+where
+    F: FnOnce(Handle) -> I,
+    I: Io,
+{
+    type Output = IoResult<I::Output>;
+
+    fn run(self) -> Self::Output {
+        let handle = try_open_file(self.path, self.mode).run()?;
+        let _close = CloseOnDrop(handle.clone());
+
+        let output = (self.f)(handle.clone()).run();
+        try_h_close(handle).run()?;
+
+        Ok(output)
+    }
+}
+
+/// Open a file, run the action built by `f` with its handle, then close it, even if the action
+/// panics
+pub fn try_with_file<F, I>(path: PathBuf, mode: IoMode, f: F) -> TryWithFileIo<F>
+where
+    F: FnOnce(Handle) -> I,
+    I: Io,
+{
+    TryWithFileIo { path, mode, f }
+}
+
+#[derive(Clone)]
+pub struct HGetLineIo {
+    handle: Handle,
+}
+
+impl Io for HGetLineIo {
+    type Output = String;
+
+    fn run(self) -> Self::Output {
+        try_h_get_line(self.handle).run().unwrap()
+    }
+}
+
+pub fn h_get_line(handle: Handle) -> HGetLineIo {
+    HGetLineIo { handle }
+}
+
+#[derive(Clone)]
+pub struct TryHGetLineIo {
+    handle: Handle,
+}
+
+impl Io for TryHGetLineIo {
+    type Output = IoResult<String>;
+
+    fn run(self) -> Self::Output {
+        let mut line = String::new();
+        self.handle.with(|inner| inner.read_line(&mut line))?;
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        Ok(line)
+    }
+}
+
+/// Read a line without its line ending; an empty string at the end of input, see `h_is_eof`
+pub fn try_h_get_line(handle: Handle) -> TryHGetLineIo {
+    TryHGetLineIo { handle }
+}
+
+#[derive(Clone)]
+pub struct HIsEofIo {
+    handle: Handle,
+}
+
+impl Io for HIsEofIo {
+    type Output = bool;
+
+    fn run(self) -> Self::Output {
+        try_h_is_eof(self.handle).run().unwrap()
+    }
+}
+
+pub fn h_is_eof(handle: Handle) -> HIsEofIo {
+    HIsEofIo { handle }
+}
+
+#[derive(Clone)]
+pub struct TryHIsEofIo {
+    handle: Handle,
+}
+
+impl Io for TryHIsEofIo {
+    type Output = IoResult<bool>;
+
+    fn run(self) -> Self::Output {
+        self.handle.with(HandleInner::is_eof)
+    }
+}
+
+/// Whether there is nothing left to read, blocking until that is known
+pub fn try_h_is_eof(handle: Handle) -> TryHIsEofIo {
+    TryHIsEofIo { handle }
+}
+
+/// The rest of a handle read lazily, one line at a time
+///
+/// Each item keeps its line ending, so the items concatenate back to the contents. A line that
+/// isn't UTF-8 is an error and ends the iteration.
+pub struct TryContents {
+    handle: Option<Handle>,
+}
+
+impl TryContents {
+    fn next_line(
+        &mut self,
+        decode: impl FnOnce(Vec<u8>) -> IoResult<String>,
+    ) -> Option<IoResult<String>> {
+        let handle = self.handle.as_ref()?;
+        let mut line = Vec::new();
+
+        match handle.with(|inner| inner.read_until(b'\n', &mut line)) {
+            Ok(0) => {
+                self.handle = None;
+                None
+            }
+            Ok(_) => match decode(line) {
+                Ok(line) => Some(Ok(line)),
+                Err(e) => {
+                    self.handle = None;
+                    Some(Err(e))
+                }
+            },
+            Err(e) => {
+                self.handle = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Iterator for TryContents {
+    type Item = IoResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line(|line| Ok(String::from_utf8(line)?))
+    }
+}
+
+/// As `TryContents`, replacing invalid UTF-8 with U+FFFD instead of failing
+pub struct Contents(TryContents);
+
+impl Iterator for Contents {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next_line(|line| Ok(String::from_utf8_lossy(&line).into_owned()))
+            .map(Result::unwrap)
+    }
+}
+
+#[derive(Clone)]
+pub struct HGetContentsIo {
+    handle: Handle,
+}
+
+impl Io for HGetContentsIo {
+    type Output = Contents;
+
+    fn run(self) -> Self::Output {
+        Contents(try_h_get_contents(self.handle).run())
+    }
+}
+
+pub fn h_get_contents(handle: Handle) -> HGetContentsIo {
+    HGetContentsIo { handle }
+}
+
+#[derive(Clone)]
+pub struct TryHGetContentsIo {
+    handle: Handle,
+}
+
+impl Io for TryHGetContentsIo {
+    type Output = TryContents;
+
+    fn run(self) -> Self::Output {
+        TryContents {
+            handle: Some(self.handle),
+        }
+    }
+}
+
+/// Read the rest of the handle lazily; nothing is read until the iterator is advanced
+pub fn try_h_get_contents(handle: Handle) -> TryHGetContentsIo {
+    TryHGetContentsIo { handle }
+}
+
+#[derive(Clone)]
+pub struct HGetSomeIo {
+    handle: Handle,
+    max: usize,
+}
+
+impl Io for HGetSomeIo {
+    type Output = Vec<u8>;
+
+    fn run(self) -> Self::Output {
+        try_h_get_some(self.handle, self.max).run().unwrap()
+    }
+}
+
+pub fn h_get_some(handle: Handle, max: usize) -> HGetSomeIo {
+    HGetSomeIo { handle, max }
+}
+
+#[derive(Clone)]
+pub struct TryHGetSomeIo {
+    handle: Handle,
+    max: usize,
+}
+
+impl Io for TryHGetSomeIo {
+    type Output = IoResult<Vec<u8>>;
+
+    fn run(self) -> Self::Output {
+        let mut bytes = vec![0; self.max];
+        let read = self.handle.with(|inner| inner.read(&mut bytes))?;
+
+        bytes.truncate(read);
+        Ok(bytes)
+    }
+}
+
+/// Read up to `max` bytes, waiting only until some are available; empty at the end of input
+pub fn try_h_get_some(handle: Handle, max: usize) -> TryHGetSomeIo {
+    TryHGetSomeIo { handle, max }
+}
+
+#[derive(Clone)]
+pub struct HGetBytesIo {
+    handle: Handle,
+}
+
+impl Io for HGetBytesIo {
+    type Output = Vec<u8>;
+
+    fn run(self) -> Self::Output {
+        try_h_get_bytes(self.handle).run().unwrap()
+    }
+}
+
+pub fn h_get_bytes(handle: Handle) -> HGetBytesIo {
+    HGetBytesIo { handle }
+}
+
+#[derive(Clone)]
+pub struct TryHGetBytesIo {
+    handle: Handle,
+}
+
+impl Io for TryHGetBytesIo {
+    type Output = IoResult<Vec<u8>>;
+
+    fn run(self) -> Self::Output {
+        let mut bytes = Vec::new();
+        self.handle.with(|inner| inner.read_to_end(&mut bytes))?;
+
+        Ok(bytes)
+    }
+}
+
+/// Read the rest of the handle as bytes, whatever their encoding
+pub fn try_h_get_bytes(handle: Handle) -> TryHGetBytesIo {
+    TryHGetBytesIo { handle }
+}
+
+#[derive(Clone)]
+pub struct HPutStrIo {
+    handle: Handle,
+    content: String,
+}
+
+impl Io for HPutStrIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_h_put_str(self.handle, self.content).run().unwrap()
+    }
+}
+
+pub fn h_put_str<S: Into<String>>(handle: Handle, content: S) -> HPutStrIo {
+    HPutStrIo {
+        handle,
+        content: content.into(),
+    }
+}
+
+#[derive(Clone)]
+pub struct TryHPutStrIo {
+    handle: Handle,
+    content: String,
+}
+
+impl Io for TryHPutStrIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        self.handle.with(|inner| inner.write_str(&self.content))
+    }
+}
+
+/// Write to the handle's buffer; use `h_flush` to make it visible straight away
+pub fn try_h_put_str<S: Into<String>>(handle: Handle, content: S) -> TryHPutStrIo {
+    TryHPutStrIo {
+        handle,
+        content: content.into(),
+    }
+}
+
+pub fn h_put_str_ln<S: Into<String>>(handle: Handle, content: S) -> HPutStrIo {
+    h_put_str(handle, content.into() + "\n")
+}
+
+pub fn try_h_put_str_ln<S: Into<String>>(handle: Handle, content: S) -> TryHPutStrIo {
+    try_h_put_str(handle, content.into() + "\n")
+}
+
+#[derive(Clone)]
+pub struct HSeekIo {
+    handle: Handle,
+    mode: SeekMode,
+    offset: i64,
+}
+
+impl Io for HSeekIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_h_seek(self.handle, self.mode, self.offset)
+            .run()
+            .unwrap()
+    }
+}
+
+pub fn h_seek(handle: Handle, mode: SeekMode, offset: i64) -> HSeekIo {
+    HSeekIo {
+        handle,
+        mode,
+        offset,
+    }
+}
+
+#[derive(Clone)]
+pub struct TryHSeekIo {
+    handle: Handle,
+    mode: SeekMode,
+    offset: i64,
+}
+
+impl Io for TryHSeekIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        let position = match self.mode {
+            SeekMode::Absolute => SeekFrom::Start(u64::try_from(self.offset).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "negative seek offset")
+            })?),
+            SeekMode::Relative => SeekFrom::Current(self.offset),
+            SeekMode::FromEnd => SeekFrom::End(self.offset),
+        };
+
+        self.handle.with(|inner| inner.seek(position).map(drop))
+    }
+}
+
+/// Move the position of a file handle; standard streams can't seek
+pub fn try_h_seek(handle: Handle, mode: SeekMode, offset: i64) -> TryHSeekIo {
+    TryHSeekIo {
+        handle,
+        mode,
+        offset,
+    }
+}
+
+#[derive(Clone)]
+pub struct HFlushIo {
+    handle: Handle,
+}
+
+impl Io for HFlushIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_h_flush(self.handle).run().unwrap()
+    }
+}
+
+pub fn h_flush(handle: Handle) -> HFlushIo {
+    HFlushIo { handle }
+}
+
+#[derive(Clone)]
+pub struct TryHFlushIo {
+    handle: Handle,
+}
+
+impl Io for TryHFlushIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        self.handle.with(HandleInner::flush)
+    }
+}
+
+pub fn try_h_flush(handle: Handle) -> TryHFlushIo {
+    TryHFlushIo { handle }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("entoli-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_try_actions_report_errors() {
+        let missing = temp_path("missing");
+        let not_utf8 = temp_path("not-utf8");
+        write_bytes(not_utf8.clone(), vec![0xff, 0xfe]).run();
+
+        assert!(matches!(
+            try_read_file(missing.clone()).run(),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+        assert!(try_read_file(not_utf8.clone()).run().is_err());
+        assert!(try_list_dir(missing.clone()).run().is_err());
+        assert!(try_remove_file(missing.clone()).run().is_err());
+        assert!(try_remove_dir_rec(missing.clone()).run().is_err());
+        assert!(try_get_permissions(missing.clone()).run().is_err());
+        assert!(try_get_modification_time(missing.clone()).run().is_err());
+        assert!(try_create_dir(std::env::temp_dir()).run().is_err());
+        assert!(try_write_file(missing.join("child"), String::new())
+            .run()
+            .is_err());
+
+        remove_file(not_utf8).run();
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let path = temp_path("bytes");
+        let content = vec![0, 159, 146, 150, 255];
+
+        write_bytes(path.clone(), content.clone()).run();
+
+        assert_eq!(read_bytes(path.clone()).run(), content);
+        assert!(try_read_file(path.clone()).run().is_err());
+        remove_file(path).run();
+    }
+
+    #[test]
+    fn test_with_file_read_write_seek() {
+        let path = temp_path("handle");
+
+        with_file(path.clone(), IoMode::Write, |h| {
+            h_put_str_ln(h.clone(), "first").then(h_put_str(h, "second\r\nthird"))
+        })
+        .run();
+
+        let (first, rest) = with_file(path.clone(), IoMode::ReadWrite, |h| {
+            let read = h_get_line(h.clone());
+            read.and_then(move |first| {
+                // Overwrite "second" in place, then read from the start again
+                h_put_str(h.clone(), "SECOND")
+                    .then(h_seek(h.clone(), SeekMode::Absolute, 0))
+                    .map(move |_| (first, h_get_contents(h).run().collect::<Vec<_>>()))
+            })
+        })
+        .run();
+
+        assert_eq!(first, "first");
+        assert_eq!(rest, vec!["first\n", "SECOND\r\n", "third"]);
+        remove_file(path).run();
+    }
+
+    #[test]
+    fn test_handle_modes_and_close() {
+        let path = temp_path("modes");
+        write_file(path.clone(), "a\n".to_string()).run();
+
+        let h = open_file(path.clone(), IoMode::Read).run();
+        assert!(try_h_put_str(h.clone(), "x").run().is_err());
+        assert_eq!(h_get_line(h.clone()).run(), "a");
+        assert!(h_is_eof(h.clone()).run());
+        h_close(h.clone()).run();
+        assert!(try_h_get_line(h.clone()).run().is_err());
+        h_close(h).run();
+
+        let h = open_file(path.clone(), IoMode::Append).run();
+        h_put_str(h.clone(), "b\n").run();
+        assert!(try_h_get_line(h.clone()).run().is_err());
+        drop(h);
+
+        assert_eq!(read_file(path.clone()).run(), "a\nb\n");
+        assert!(try_h_seek(stdout(), SeekMode::Absolute, 0).run().is_err());
+        remove_file(path).run();
+    }
+
+    #[test]
+    fn test_non_utf8_contents_and_bytes() {
+        let path = temp_path("contents-bytes");
+        write_bytes(path.clone(), b"ok\n\xffbad\nrest".to_vec()).run();
+
+        let h = open_file(path.clone(), IoMode::Read).run();
+        let strict = try_h_get_contents(h).run().collect::<Vec<_>>();
+        assert_eq!(strict.len(), 2);
+        assert_eq!(strict[0].as_deref().ok(), Some("ok\n"));
+        assert!(matches!(strict[1], Err(Error::Utf8(_))));
+
+        let h = open_file(path.clone(), IoMode::Read).run();
+        let lossy = h_get_contents(h).run().collect::<Vec<_>>();
+        assert_eq!(lossy, vec!["ok\n", "\u{fffd}bad\n", "rest"]);
+
+        let h = open_file(path.clone(), IoMode::Read).run();
+        assert_eq!(h_get_some(h.clone(), 2).run(), b"ok");
+        assert_eq!(h_get_line(h.clone()).run(), "");
+        assert_eq!(h_get_bytes(h.clone()).run(), b"\xffbad\nrest");
+        assert!(h_get_some(h.clone(), 16).run().is_empty());
+        h_close(h.clone()).run();
+        assert!(try_h_get_bytes(h.clone()).run().is_err());
+        assert!(try_h_get_some(h, 1).run().is_err());
+
+        assert!(try_h_get_bytes(stdout()).run().is_err());
+        remove_file(path).run();
+    }
+
+    #[test]
+    fn test_standard_handles_are_shared() {
+        assert!(Arc::ptr_eq(&stdin().inner, &stdin().inner));
+        assert!(Arc::ptr_eq(&stdout().inner, &stdout().inner));
+        assert!(Arc::ptr_eq(&stderr().inner, &stderr().inner));
+
+        // Closing one clone closes every clone of the same handle
+        let path = temp_path("shared");
+        write_file(path.clone(), "a\n".to_string()).run();
+        let h = open_file(path.clone(), IoMode::Read).run();
+        let other = h.clone();
+        assert!(Arc::ptr_eq(&h.inner, &other.inner));
+        h_close(h).run();
+        assert!(try_h_get_line(other.clone()).run().is_err());
+        assert!(try_h_is_eof(other).run().is_err());
+        remove_file(path).run();
+    }
+}
@@ -1,2 +1,6 @@
+pub mod environment;
+pub mod exit;
 pub mod io;
 pub mod process;
+pub mod runner;
+pub mod shell;
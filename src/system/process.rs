@@ -1,71 +1,190 @@
-use std::{io::Write, path::PathBuf};
-
-use crate::prelude::Io;
+use std::{
+    io::Write,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use super::{runner::current_runner, shell as sh};
+use crate::{
+    control::concurrent::{current_thread_killed, CANCEL_POLL},
+    error::{Error, IoResult},
+    prelude::Io,
+};
 
 pub struct CreateProcess {
     cmd_spec: CmdSpec,
     cwd: Option<PathBuf>,
     env: Option<Vec<(String, String)>>,
+    inherit_env: bool,
     std_in: StdStream,
     std_out: StdStream,
     std_err: StdStream,
+    create_group: bool,
+    child_user: Option<u32>,
+    child_group: Option<u32>,
+    umask: Option<u32>,
+    new_session: bool,
+    close_fds: bool,
+    cpu_time_limit: Option<u64>,
+    address_space_limit: Option<u64>,
+    open_files_limit: Option<u64>,
+    timeout: Option<std::time::Duration>,
 }
 
-/// Either a shell command or a raw command
-///
-/// Shell commands are executed in a shell
-///
-/// Raw command is executable path and arguments
+impl CreateProcess {
+    pub fn new(cmd_spec: CmdSpec) -> Self {
+        CreateProcess {
+            cmd_spec,
+            cwd: None,
+            env: None,
+            inherit_env: true,
+            std_in: StdStream::Inherit,
+            std_out: StdStream::Inherit,
+            std_err: StdStream::Inherit,
+            create_group: false,
+            child_user: None,
+            child_group: None,
+            umask: None,
+            new_session: false,
+            close_fds: false,
+            cpu_time_limit: None,
+            address_space_limit: None,
+            open_files_limit: None,
+            timeout: None,
+        }
+    }
 
-pub fn create_process(process: CreateProcess) -> CreateProcessIo {
-    CreateProcessIo { process }
-}
+    pub fn with_cwd<P: Into<PathBuf>>(mut self, cwd: P) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
 
-pub fn shell<S: Into<String>>(cmd: S) -> CreateProcess {
-    CreateProcess {
-        cmd_spec: CmdSpec::Shell(cmd.into()),
-        cwd: None,
-        env: None,
-        std_in: StdStream::Inherit,
-        std_out: StdStream::Inherit,
-        std_err: StdStream::Inherit,
+    /// Replace the whole environment of the child
+    pub fn with_env(mut self, env: Vec<(String, String)>) -> Self {
+        self.inherit_env = false;
+        self.env = Some(env);
+        self
     }
-}
 
-pub fn proc(exec: PathBuf, args: Vec<String>) -> CreateProcess {
-    CreateProcess {
-        cmd_spec: CmdSpec::Raw(exec, args),
-        cwd: None,
-        env: None,
-        std_in: StdStream::Inherit,
-        std_out: StdStream::Inherit,
-        std_err: StdStream::Inherit,
+    /// Set one variable on top of the inherited environment
+    pub fn add_env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env
+            .get_or_insert_with(Vec::new)
+            .push((key.into(), value.into()));
+        self
     }
-}
 
-#[derive(Clone)]
-pub enum CmdSpec {
-    Shell(String),
-    Raw(PathBuf, Vec<String>),
-}
+    /// Start the child with an empty environment
+    pub fn clear_env(mut self) -> Self {
+        self.inherit_env = false;
+        self.env = None;
+        self
+    }
 
-// ! #[derive(Clone)]
-pub enum StdStream {
-    Inherit,
-    UseHandle(std::process::Stdio),
-    CreatePipe,
-    NoStream,
-}
+    pub fn with_stdin(mut self, stream: StdStream) -> Self {
+        self.std_in = stream;
+        self
+    }
 
-pub struct CreateProcessIo {
-    process: CreateProcess,
-}
+    pub fn with_stdout(mut self, stream: StdStream) -> Self {
+        self.std_out = stream;
+        self
+    }
 
-impl Io for CreateProcessIo {
-    type Output = std::process::Child; // Rust already has good abstractions for processes instead of tuples
+    pub fn with_stderr(mut self, stream: StdStream) -> Self {
+        self.std_err = stream;
+        self
+    }
 
-    fn run(self) -> Self::Output {
-        let mut command = match self.process.cmd_spec {
+    /// Start the child in a new process group
+    pub fn create_group(mut self, create_group: bool) -> Self {
+        self.create_group = create_group;
+        self
+    }
+
+    /// Run the child as this user id, Unix only
+    pub fn child_user(mut self, uid: u32) -> Self {
+        self.child_user = Some(uid);
+        self
+    }
+
+    /// Run the child with this group id, Unix only
+    pub fn child_group(mut self, gid: u32) -> Self {
+        self.child_group = Some(gid);
+        self
+    }
+
+    /// Set the file mode creation mask of the child, Unix only
+    pub fn umask(mut self, umask: u32) -> Self {
+        self.umask = Some(umask);
+        self
+    }
+
+    /// Start the child in a new session with `setsid`, which also gives it a new process group,
+    /// Unix only
+    pub fn new_session(mut self, new_session: bool) -> Self {
+        self.new_session = new_session;
+        self
+    }
+
+    /// Close every inherited file descriptor except the standard streams, Unix only
+    pub fn close_fds(mut self, close_fds: bool) -> Self {
+        self.close_fds = close_fds;
+        self
+    }
+
+    /// Limit the CPU time of the child, rounded up to whole seconds, Unix only
+    ///
+    /// The child gets SIGXCPU and then SIGKILL once it is used up. Like the other limits, this is
+    /// clamped to the hard limit the caller already has, which only a privileged process can raise.
+    pub fn cpu_time_limit(mut self, limit: std::time::Duration) -> Self {
+        let secs = limit.as_secs() + u64::from(limit.subsec_nanos() > 0);
+        self.cpu_time_limit = Some(secs);
+        self
+    }
+
+    /// Limit the virtual memory of the child to `bytes`, Unix only
+    pub fn address_space_limit(mut self, bytes: u64) -> Self {
+        self.address_space_limit = Some(bytes);
+        self
+    }
+
+    /// Limit the number of file descriptors the child can have open, Unix only
+    pub fn open_files_limit(mut self, limit: u64) -> Self {
+        self.open_files_limit = Some(limit);
+        self
+    }
+
+    /// Kill the child if it is still running after `timeout` of wall-clock time, Unix only
+    ///
+    /// A killed child exits with `ExitCode::Signaled(SIGKILL, false)`. With `create_group` or
+    /// `new_session` its whole process group is killed, so the processes it started can't outlive
+    /// it and keep its pipes open.
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cmd_spec(&self) -> &CmdSpec {
+        &self.cmd_spec
+    }
+
+    pub fn get_cwd(&self) -> Option<&PathBuf> {
+        self.cwd.as_ref()
+    }
+
+    pub fn get_env(&self) -> Option<&[(String, String)]> {
+        self.env.as_deref()
+    }
+
+    /// The stdin, stdout and stderr settings, for runners which don't start a real process
+    pub(crate) fn into_std_streams(self) -> (StdStream, StdStream, StdStream) {
+        (self.std_in, self.std_out, self.std_err)
+    }
+
+    pub(crate) fn command(self) -> std::process::Command {
+        let mut command = match self.cmd_spec {
             CmdSpec::Shell(cmd) => {
                 let mut command = std::process::Command::new("sh");
                 command.arg("-c").arg(cmd);
@@ -78,15 +197,102 @@ impl Io for CreateProcessIo {
             }
         };
 
-        if let Some(cwd) = self.process.cwd {
+        if let Some(cwd) = self.cwd {
             command.current_dir(cwd);
         }
 
-        if let Some(env) = self.process.env {
+        if !self.inherit_env {
+            command.env_clear();
+        }
+
+        if let Some(env) = self.env {
             command.envs(env);
         }
 
-        match self.process.std_in {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            // setsid fails in a process group leader, and makes a new group anyway
+            if self.create_group && !self.new_session {
+                command.process_group(0);
+            }
+
+            if let Some(uid) = self.child_user {
+                command.uid(uid);
+            }
+
+            if let Some(gid) = self.child_group {
+                command.gid(gid);
+            }
+
+            if let Some(umask) = self.umask {
+                // SAFETY: umask is async-signal-safe
+                unsafe {
+                    command.pre_exec(move || {
+                        libc::umask(umask as libc::mode_t);
+                        Ok(())
+                    });
+                }
+            }
+
+            let new_session = self.new_session;
+            let close_fds = self.close_fds;
+            let limits = [
+                (libc::RLIMIT_CPU, self.cpu_time_limit),
+                (libc::RLIMIT_AS, self.address_space_limit),
+                (libc::RLIMIT_NOFILE, self.open_files_limit),
+            ];
+
+            if new_session || close_fds || limits.iter().any(|(_, limit)| limit.is_some()) {
+                // SAFETY: setsid, fcntl, close_range, getrlimit and setrlimit are async-signal-safe
+                unsafe {
+                    command.pre_exec(move || {
+                        if new_session && libc::setsid() == -1 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+
+                        if close_fds {
+                            set_cloexec_from(3);
+                        }
+
+                        for (resource, limit) in limits {
+                            if let Some(limit) = limit {
+                                // Raising the hard limit needs privileges, so stay under it
+                                let mut current: libc::rlimit = std::mem::zeroed();
+                                if libc::getrlimit(resource, &mut current) == -1 {
+                                    return Err(std::io::Error::last_os_error());
+                                }
+                                let limit = (limit as libc::rlim_t).min(current.rlim_max);
+
+                                let rlimit = libc::rlimit {
+                                    rlim_cur: limit,
+                                    rlim_max: limit,
+                                };
+                                if libc::setrlimit(resource, &rlimit) == -1 {
+                                    return Err(std::io::Error::last_os_error());
+                                }
+                            }
+                        }
+
+                        Ok(())
+                    });
+                }
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+            if self.create_group {
+                command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+            }
+        }
+
+        match self.std_in {
             StdStream::Inherit => {
                 command.stdin(std::process::Stdio::inherit());
             }
@@ -101,7 +307,7 @@ impl Io for CreateProcessIo {
             }
         }
 
-        match self.process.std_out {
+        match self.std_out {
             StdStream::Inherit => {
                 command.stdout(std::process::Stdio::inherit());
             }
@@ -116,7 +322,7 @@ impl Io for CreateProcessIo {
             }
         }
 
-        match self.process.std_err {
+        match self.std_err {
             StdStream::Inherit => {
                 command.stderr(std::process::Stdio::inherit());
             }
@@ -131,10 +337,282 @@ impl Io for CreateProcessIo {
             }
         }
 
-        command.spawn().unwrap()
+        command
+    }
+
+    /// Spawn the child and start the watchdog enforcing its timeout
+    pub(crate) fn spawn(mut self) -> std::io::Result<Child> {
+        let timeout = self.timeout.take();
+        let group = self.create_group || self.new_session;
+        let child = self.command().spawn()?;
+
+        #[cfg(unix)]
+        if let Some(timeout) = timeout {
+            kill_after(child.id(), timeout, group);
+        }
+
+        #[cfg(not(unix))]
+        let _ = group;
+
+        #[cfg(not(unix))]
+        let _ = timeout;
+
+        Ok(child.into())
+    }
+}
+
+/// Mark every file descriptor from `low` up as close-on-exec
+///
+/// Runs between fork and exec, so it must not allocate. Marking instead of closing keeps the
+/// descriptor std uses to report exec failures working.
+#[cfg(unix)]
+unsafe fn set_cloexec_from(low: libc::c_int) {
+    #[cfg(target_os = "linux")]
+    if libc::syscall(
+        libc::SYS_close_range,
+        low as libc::c_uint,
+        libc::c_uint::MAX,
+        libc::CLOSE_RANGE_CLOEXEC,
+    ) == 0
+    {
+        return;
+    }
+
+    let max = match libc::sysconf(libc::_SC_OPEN_MAX) {
+        -1 => 1024,
+        max => max.min(1 << 16) as libc::c_int,
+    };
+    for fd in low..max {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags != -1 {
+            libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC);
+        }
+    }
+}
+
+/// Kill `pid` with SIGKILL unless it exits within `timeout`, along with the process group it
+/// leads if `group` is set
+///
+/// On Linux a pidfd makes sure the signal can't reach a reused pid. Elsewhere the watchdog polls
+/// without reaping and only signals a child which hasn't exited yet. Either way the child is still
+/// unreaped when signalled, so its pid and group id can't have been reused.
+#[cfg(unix)]
+fn kill_after(pid: u32, timeout: std::time::Duration, group: bool) {
+    let pid = pid as libc::pid_t;
+
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: pidfd_open only creates a descriptor, closed below
+        let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        if pidfd >= 0 {
+            let pidfd = pidfd as libc::c_int;
+            std::thread::spawn(move || {
+                let mut poll = libc::pollfd {
+                    fd: pidfd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let deadline = std::time::Instant::now() + timeout;
+                let mut ready;
+                loop {
+                    let left = deadline.saturating_duration_since(std::time::Instant::now());
+                    let millis = left.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+                    // SAFETY: `poll` is one valid pollfd
+                    ready = unsafe { libc::poll(&mut poll, 1, millis) };
+                    let interrupted = ready == -1
+                        && std::io::Error::last_os_error().kind()
+                            == std::io::ErrorKind::Interrupted;
+                    if !interrupted {
+                        break;
+                    }
+                }
+                // SAFETY: signalling has no memory effects, and the pidfd is ours to close
+                if ready == 0 {
+                    if group {
+                        // The leader hasn't exited, so the group still has its id
+                        unsafe { libc::killpg(pid, libc::SIGKILL) };
+                    } else {
+                        unsafe {
+                            libc::syscall(
+                                libc::SYS_pidfd_send_signal,
+                                pidfd,
+                                libc::SIGKILL,
+                                std::ptr::null::<libc::siginfo_t>(),
+                                0,
+                            )
+                        };
+                    }
+                }
+                unsafe { libc::close(pidfd) };
+            });
+            return;
+        }
+    }
+
+    std::thread::spawn(move || {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            // SAFETY: siginfo_t is plain data, for which all zeroes is valid
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let flags = libc::WEXITED | libc::WNOWAIT | libc::WNOHANG;
+
+            // Stop once the child has exited, which fills in `info`, or was reaped (ECHILD)
+            // SAFETY: `info` is a valid siginfo_t, and WNOWAIT leaves the child unreaped
+            if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == -1 {
+                if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                    return;
+                }
+            } else if info.si_signo != 0 {
+                return;
+            }
+
+            if std::time::Instant::now() >= deadline {
+                // SAFETY: signalling has no memory effects, and the child is still unreaped
+                unsafe {
+                    if group {
+                        libc::killpg(pid, libc::SIGKILL);
+                    } else {
+                        libc::kill(pid, libc::SIGKILL);
+                    }
+                }
+                return;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    });
+}
+
+/// Either a shell command or a raw command
+///
+/// Shell commands are executed in a shell
+///
+/// Raw command is executable path and arguments
+pub fn create_process(process: CreateProcess) -> CreateProcessIo {
+    CreateProcessIo { process }
+}
+
+pub fn try_create_process(process: CreateProcess) -> TryCreateProcessIo {
+    TryCreateProcessIo { process }
+}
+
+pub fn shell<S: Into<String>>(cmd: S) -> CreateProcess {
+    CreateProcess::new(CmdSpec::Shell(cmd.into()))
+}
+
+pub fn proc(exec: PathBuf, args: Vec<String>) -> CreateProcess {
+    CreateProcess::new(CmdSpec::Raw(exec, args))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CmdSpec {
+    Shell(String),
+    Raw(PathBuf, Vec<String>),
+}
+
+impl CmdSpec {
+    /// Parse a command line into a `Raw` spec, splitting it into words as `sh` would
+    pub fn parse(line: &str) -> Result<CmdSpec, sh::ParseError> {
+        let mut words = sh::split(line)?.into_iter();
+        let exec = words.next().ok_or(sh::ParseError::Empty)?;
+
+        Ok(CmdSpec::Raw(exec.into(), words.collect()))
+    }
+
+    /// Render as a line `sh` runs as the same command; `Raw` words are quoted so it round-trips
+    pub fn to_shell_string(&self) -> String {
+        match self {
+            CmdSpec::Shell(cmd) => cmd.clone(),
+            CmdSpec::Raw(exec, args) => show_command_for_user(exec.clone(), args.clone()),
+        }
+    }
+}
+
+// ! #[derive(Clone)]
+pub enum StdStream {
+    Inherit,
+    UseHandle(std::process::Stdio),
+    CreatePipe,
+    NoStream,
+}
+
+pub struct CreateProcessIo {
+    process: CreateProcess,
+}
+
+impl Io for CreateProcessIo {
+    type Output = Child; // Rust already has good abstractions for processes instead of tuples
+
+    fn run(self) -> Self::Output {
+        try_create_process(self.process).run().unwrap()
+    }
+}
+
+pub struct TryCreateProcessIo {
+    process: CreateProcess,
+}
+
+impl Io for TryCreateProcessIo {
+    type Output = IoResult<Child>;
+
+    fn run(self) -> Self::Output {
+        current_runner().spawn(self.process)
+    }
+}
+
+type StdinWriter = std::thread::JoinHandle<std::io::Result<()>>;
+
+fn write_stdin(pipe: Option<std::process::ChildStdin>, stdin: Vec<u8>) -> Option<StdinWriter> {
+    pipe.map(|mut pipe| std::thread::spawn(move || pipe.write_all(&stdin)))
+}
+
+fn join_stdin(writer: Option<StdinWriter>) -> IoResult<()> {
+    match writer.map(|writer| writer.join().unwrap()) {
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
     }
 }
 
+type OutputReader = std::thread::JoinHandle<std::io::Result<Vec<u8>>>;
+
+fn read_output<R>(pipe: Option<R>) -> Option<OutputReader>
+where
+    R: std::io::Read + Send + 'static,
+{
+    pipe.map(|mut pipe| {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            pipe.read_to_end(&mut output).map(|_| output)
+        })
+    })
+}
+
+fn join_output(reader: Option<OutputReader>) -> IoResult<Vec<u8>> {
+    Ok(reader
+        .map(|reader| reader.join().unwrap())
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Feed `stdin` from another thread while collecting stdout and stderr,
+/// so a child filling its output pipe can't deadlock against a large input.
+///
+/// The child is waited on through a `ProcessHandle`, so killing the calling thread kills it.
+pub(crate) fn communicate(mut child: Child, stdin: Vec<u8>) -> IoResult<std::process::Output> {
+    let writer = write_stdin(child.stdin.take(), stdin);
+    let stdout = read_output(child.stdout.take());
+    let stderr = read_output(child.stderr.take());
+
+    let status = ProcessHandle::new(child).wait()?;
+    join_stdin(writer)?;
+
+    Ok(std::process::Output {
+        status,
+        stdout: join_output(stdout)?,
+        stderr: join_output(stderr)?,
+    })
+}
+
 // Simpler functions for common tasks
 #[derive(Clone)]
 pub struct CallProcessIo {
@@ -146,14 +624,7 @@ impl Io for CallProcessIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        let status = std::process::Command::new(self.exec)
-            .args(self.args)
-            .status()
-            .unwrap();
-
-        if !status.success() {
-            panic!("Command failed with exit code: {}", status);
-        }
+        try_call_process(self.exec, self.args).run().unwrap()
     }
 }
 
@@ -161,6 +632,32 @@ pub fn call_process(exec: PathBuf, args: Vec<String>) -> CallProcessIo {
     CallProcessIo { exec, args }
 }
 
+#[derive(Clone)]
+pub struct TryCallProcessIo {
+    exec: PathBuf,
+    args: Vec<String>,
+}
+
+impl Io for TryCallProcessIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        let status = current_runner()
+            .output(proc(self.exec, self.args), Vec::new())?
+            .status;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::ProcessFailed(status))
+        }
+    }
+}
+
+pub fn try_call_process(exec: PathBuf, args: Vec<String>) -> TryCallProcessIo {
+    TryCallProcessIo { exec, args }
+}
+
 #[derive(Clone)]
 pub struct CallCommandIo {
     command: String,
@@ -170,15 +667,7 @@ impl Io for CallCommandIo {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        let status = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(&self.command)
-            .status()
-            .unwrap();
-
-        if !status.success() {
-            panic!("Command failed with exit code: {}", status);
-        }
+        try_call_command(self.command).run().unwrap()
     }
 }
 
@@ -186,6 +675,31 @@ pub fn call_command(command: String) -> CallCommandIo {
     CallCommandIo { command }
 }
 
+#[derive(Clone)]
+pub struct TryCallCommandIo {
+    command: String,
+}
+
+impl Io for TryCallCommandIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        let status = current_runner()
+            .output(shell(self.command), Vec::new())?
+            .status;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::ProcessFailed(status))
+        }
+    }
+}
+
+pub fn try_call_command(command: String) -> TryCallCommandIo {
+    TryCallCommandIo { command }
+}
+
 #[derive(Clone)]
 pub struct SpawnProcessIo {
     exec: PathBuf,
@@ -193,13 +707,10 @@ pub struct SpawnProcessIo {
 }
 
 impl Io for SpawnProcessIo {
-    type Output = std::process::Child;
+    type Output = Child;
 
     fn run(self) -> Self::Output {
-        std::process::Command::new(self.exec)
-            .args(self.args)
-            .spawn()
-            .unwrap()
+        try_spawn_process(self.exec, self.args).run().unwrap()
     }
 }
 
@@ -207,20 +718,34 @@ pub fn spawn_process(exec: PathBuf, args: Vec<String>) -> SpawnProcessIo {
     SpawnProcessIo { exec, args }
 }
 
+#[derive(Clone)]
+pub struct TrySpawnProcessIo {
+    exec: PathBuf,
+    args: Vec<String>,
+}
+
+impl Io for TrySpawnProcessIo {
+    type Output = IoResult<Child>;
+
+    fn run(self) -> Self::Output {
+        current_runner().spawn(proc(self.exec, self.args))
+    }
+}
+
+pub fn try_spawn_process(exec: PathBuf, args: Vec<String>) -> TrySpawnProcessIo {
+    TrySpawnProcessIo { exec, args }
+}
+
 #[derive(Clone)]
 pub struct SpawnCommandIo {
     command: String,
 }
 
 impl Io for SpawnCommandIo {
-    type Output = std::process::Child;
+    type Output = Child;
 
     fn run(self) -> Self::Output {
-        std::process::Command::new("sh")
-            .arg("-c")
-            .arg(&self.command)
-            .spawn()
-            .unwrap()
+        try_spawn_command(self.command).run().unwrap()
     }
 }
 
@@ -228,6 +753,23 @@ pub fn spawn_command(command: String) -> SpawnCommandIo {
     SpawnCommandIo { command }
 }
 
+#[derive(Clone)]
+pub struct TrySpawnCommandIo {
+    command: String,
+}
+
+impl Io for TrySpawnCommandIo {
+    type Output = IoResult<Child>;
+
+    fn run(self) -> Self::Output {
+        current_runner().spawn(shell(self.command))
+    }
+}
+
+pub fn try_spawn_command(command: String) -> TrySpawnCommandIo {
+    TrySpawnCommandIo { command }
+}
+
 pub struct ReadCreateProcessIo {
     process: CreateProcess,
     stdin: String,
@@ -237,58 +779,33 @@ impl Io for ReadCreateProcessIo {
     type Output = String;
 
     fn run(self) -> Self::Output {
-        let mut command = match self.process.cmd_spec {
-            CmdSpec::Shell(cmd) => {
-                let mut command = std::process::Command::new("sh");
-                command.arg("-c").arg(cmd);
-                command
-            }
-            CmdSpec::Raw(path, args) => {
-                let mut command = std::process::Command::new(path);
-                command.args(args);
-                command
-            }
-        };
-
-        if let Some(cwd) = self.process.cwd {
-            command.current_dir(cwd);
-        }
-
-        if let Some(env) = self.process.env {
-            command.envs(env);
-        }
-
-        if let StdStream::CreatePipe = self.process.std_in {
-            command.stdin(std::process::Stdio::piped());
-        }
-
-        if let StdStream::CreatePipe = self.process.std_out {
-            command.stdout(std::process::Stdio::piped());
-        }
+        try_read_create_process(self.process, self.stdin)
+            .run()
+            .unwrap()
+    }
+}
 
-        if let StdStream::CreatePipe = self.process.std_err {
-            command.stderr(std::process::Stdio::piped());
-        }
+pub fn read_create_process(process: CreateProcess, stdin: String) -> ReadCreateProcessIo {
+    ReadCreateProcessIo { process, stdin }
+}
 
-        let mut child = command.spawn().unwrap();
+pub struct TryReadCreateProcessIo {
+    process: CreateProcess,
+    stdin: String,
+}
 
-        if let StdStream::CreatePipe = self.process.std_in {
-            child
-                .stdin
-                .as_mut()
-                .unwrap()
-                .write_all(self.stdin.as_bytes())
-                .unwrap();
-        };
+impl Io for TryReadCreateProcessIo {
+    type Output = IoResult<String>;
 
-        let output = child.wait_with_output().unwrap();
+    fn run(self) -> Self::Output {
+        let output = current_runner().output(self.process, self.stdin.into_bytes())?;
 
-        String::from_utf8(output.stdout).unwrap()
+        Ok(String::from_utf8(output.stdout)?)
     }
 }
 
-pub fn read_create_process(process: CreateProcess, stdin: String) -> ReadCreateProcessIo {
-    ReadCreateProcessIo { process, stdin }
+pub fn try_read_create_process(process: CreateProcess, stdin: String) -> TryReadCreateProcessIo {
+    TryReadCreateProcessIo { process, stdin }
 }
 
 #[derive(Clone)]
@@ -302,25 +819,9 @@ impl Io for ReadProcess {
     type Output = String;
 
     fn run(self) -> Self::Output {
-        let mut command = std::process::Command::new(self.exec);
-        command.args(self.args);
-
-        command.stdin(std::process::Stdio::piped());
-        command.stdout(std::process::Stdio::piped());
-        command.stderr(std::process::Stdio::piped());
-
-        let mut child = command.spawn().unwrap();
-
-        child
-            .stdin
-            .as_mut()
+        try_read_process(self.exec, self.args, self.stdin)
+            .run()
             .unwrap()
-            .write_all(self.stdin.as_bytes())
-            .unwrap();
-
-        let output = child.wait_with_output().unwrap();
-
-        String::from_utf8(output.stdout).unwrap()
     }
 }
 
@@ -328,65 +829,61 @@ pub fn read_process(exec: PathBuf, args: Vec<String>, stdin: String) -> ReadProc
     ReadProcess { exec, args, stdin }
 }
 
-pub struct ReadCreateProcessWithExitCode {
-    process: CreateProcess,
+#[derive(Clone)]
+pub struct TryReadProcess {
+    exec: PathBuf,
+    args: Vec<String>,
     stdin: String,
 }
 
-impl Io for ReadCreateProcessWithExitCode {
-    type Output = (String, i32);
+impl Io for TryReadProcess {
+    type Output = IoResult<String>;
 
     fn run(self) -> Self::Output {
-        let mut command = match self.process.cmd_spec {
-            CmdSpec::Shell(cmd) => {
-                let mut command = std::process::Command::new("sh");
-                command.arg("-c").arg(cmd);
-                command
-            }
-            CmdSpec::Raw(path, args) => {
-                let mut command = std::process::Command::new(path);
-                command.args(args);
-                command
-            }
-        };
+        let process = proc(self.exec, self.args)
+            .with_stdin(StdStream::CreatePipe)
+            .with_stdout(StdStream::CreatePipe)
+            .with_stderr(StdStream::CreatePipe);
+        let output = current_runner().output(process, self.stdin.into_bytes())?;
 
-        if let Some(cwd) = self.process.cwd {
-            command.current_dir(cwd);
-        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
 
-        if let Some(env) = self.process.env {
-            command.envs(env);
-        }
+pub fn try_read_process(exec: PathBuf, args: Vec<String>, stdin: String) -> TryReadProcess {
+    TryReadProcess { exec, args, stdin }
+}
 
-        if let StdStream::CreatePipe = self.process.std_in {
-            command.stdin(std::process::Stdio::piped());
-        }
+pub struct ReadCreateProcessWithExitCode {
+    process: CreateProcess,
+    stdin: String,
+}
 
-        if let StdStream::CreatePipe = self.process.std_out {
-            command.stdout(std::process::Stdio::piped());
-        }
+impl Io for ReadCreateProcessWithExitCode {
+    type Output = (String, ExitCode);
 
-        if let StdStream::CreatePipe = self.process.std_err {
-            command.stderr(std::process::Stdio::piped());
-        }
+    fn run(self) -> Self::Output {
+        try_read_create_process_with_exit_code(self.process, self.stdin)
+            .run()
+            .unwrap()
+    }
+}
 
-        let mut child = command.spawn().unwrap();
+pub struct TryReadCreateProcessWithExitCode {
+    process: CreateProcess,
+    stdin: String,
+}
 
-        if let StdStream::CreatePipe = self.process.std_in {
-            child
-                .stdin
-                .as_mut()
-                .unwrap()
-                .write_all(self.stdin.as_bytes())
-                .unwrap();
-        };
+impl Io for TryReadCreateProcessWithExitCode {
+    type Output = IoResult<(String, ExitCode)>;
 
-        let output = child.wait_with_output().unwrap();
+    fn run(self) -> Self::Output {
+        let output = current_runner().output(self.process, self.stdin.into_bytes())?;
 
-        (
-            String::from_utf8(output.stdout).unwrap(),
-            output.status.code().unwrap(),
-        )
+        Ok((
+            String::from_utf8(output.stdout)?,
+            ExitCode::from(output.status),
+        ))
     }
 }
 
@@ -398,31 +895,36 @@ pub struct ReadProcessWithExitCode {
 }
 
 impl Io for ReadProcessWithExitCode {
-    type Output = (String, i32);
+    type Output = (String, ExitCode);
 
     fn run(self) -> Self::Output {
-        let mut command = std::process::Command::new(self.exec);
-        command.args(self.args);
-
-        command.stdin(std::process::Stdio::piped());
-        command.stdout(std::process::Stdio::piped());
-        command.stderr(std::process::Stdio::piped());
-
-        let mut child = command.spawn().unwrap();
-
-        child
-            .stdin
-            .as_mut()
+        try_read_process_with_exit_code(self.exec, self.args, self.stdin)
+            .run()
             .unwrap()
-            .write_all(self.stdin.as_bytes())
-            .unwrap();
+    }
+}
 
-        let output = child.wait_with_output().unwrap();
+#[derive(Clone)]
+pub struct TryReadProcessWithExitCode {
+    exec: PathBuf,
+    args: Vec<String>,
+    stdin: String,
+}
 
-        (
-            String::from_utf8(output.stdout).unwrap(),
-            output.status.code().unwrap(),
-        )
+impl Io for TryReadProcessWithExitCode {
+    type Output = IoResult<(String, ExitCode)>;
+
+    fn run(self) -> Self::Output {
+        let process = proc(self.exec, self.args)
+            .with_stdin(StdStream::CreatePipe)
+            .with_stdout(StdStream::CreatePipe)
+            .with_stderr(StdStream::CreatePipe);
+        let output = current_runner().output(process, self.stdin.into_bytes())?;
+
+        Ok((
+            String::from_utf8(output.stdout)?,
+            ExitCode::from(output.status),
+        ))
     }
 }
 
@@ -433,119 +935,2188 @@ pub fn read_create_process_with_exit_code(
     ReadCreateProcessWithExitCode { process, stdin }
 }
 
-// todo withCreateProcess withCreateProcess :: CreateProcess -> (Maybe Handle -> Maybe Handle -> Maybe Handle -> ProcessHandle -> IO a) -> IO a
+pub fn try_read_create_process_with_exit_code(
+    process: CreateProcess,
+    stdin: String,
+) -> TryReadCreateProcessWithExitCode {
+    TryReadCreateProcessWithExitCode { process, stdin }
+}
 
-// todo cleanupProcess :: (Maybe Handle, Maybe Handle, Maybe Handle, ProcessHandle) -> IO ()
+pub fn read_process_with_exit_code(
+    exec: PathBuf,
+    args: Vec<String>,
+    stdin: String,
+) -> ReadProcessWithExitCode {
+    ReadProcessWithExitCode { exec, args, stdin }
+}
 
-// Related utilities
+pub fn try_read_process_with_exit_code(
+    exec: PathBuf,
+    args: Vec<String>,
+    stdin: String,
+) -> TryReadProcessWithExitCode {
+    TryReadProcessWithExitCode { exec, args, stdin }
+}
 
-pub fn show_command_for_user(exec: PathBuf, args: Vec<String>) -> String {
-    format!("{} {}", exec.display(), args.join(" "))
+/// Exit code with everything a process wrote to stdout and stderr
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessOutput {
+    pub exit_code: ExitCode,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
 }
 
-pub struct GetPid {
-    process: std::process::Child,
+impl ProcessOutput {
+    pub fn stdout_string(&self) -> IoResult<String> {
+        Ok(String::from_utf8(self.stdout.clone())?)
+    }
+
+    pub fn stderr_string(&self) -> IoResult<String> {
+        Ok(String::from_utf8(self.stderr.clone())?)
+    }
+
+    /// Decode stdout, replacing invalid UTF-8 with U+FFFD
+    pub fn stdout_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    /// Decode stderr, replacing invalid UTF-8 with U+FFFD
+    pub fn stderr_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
 }
 
-impl Io for GetPid {
-    type Output = u32;
+pub struct ReadCreateProcessWithOutputIo {
+    process: CreateProcess,
+    stdin: Vec<u8>,
+}
+
+impl Io for ReadCreateProcessWithOutputIo {
+    type Output = ProcessOutput;
 
     fn run(self) -> Self::Output {
-        self.process.id()
+        try_read_create_process_with_output(self.process, self.stdin)
+            .run()
+            .unwrap()
     }
 }
 
-pub fn get_pid(process: std::process::Child) -> GetPid {
-    GetPid { process }
+/// Run a process to completion, capturing stdout and stderr as raw bytes
+///
+/// All three standard streams are piped regardless of the `CreateProcess` settings.
+pub fn read_create_process_with_output(
+    process: CreateProcess,
+    stdin: Vec<u8>,
+) -> ReadCreateProcessWithOutputIo {
+    ReadCreateProcessWithOutputIo { process, stdin }
 }
 
-#[derive(Clone)]
-pub struct GetCurrentPid;
+pub struct TryReadCreateProcessWithOutputIo {
+    process: CreateProcess,
+    stdin: Vec<u8>,
+}
 
-impl Io for GetCurrentPid {
-    type Output = u32;
+impl Io for TryReadCreateProcessWithOutputIo {
+    type Output = IoResult<ProcessOutput>;
 
     fn run(self) -> Self::Output {
-        std::process::id()
+        let process = self
+            .process
+            .with_stdin(StdStream::CreatePipe)
+            .with_stdout(StdStream::CreatePipe)
+            .with_stderr(StdStream::CreatePipe);
+        let output = current_runner().output(process, self.stdin)?;
+
+        Ok(ProcessOutput {
+            exit_code: ExitCode::from(output.status),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
     }
 }
 
-pub fn get_current_pid() -> GetCurrentPid {
-    GetCurrentPid
+pub fn try_read_create_process_with_output(
+    process: CreateProcess,
+    stdin: Vec<u8>,
+) -> TryReadCreateProcessWithOutputIo {
+    TryReadCreateProcessWithOutputIo { process, stdin }
 }
 
-// Process completion
+// Streaming output
 
-pub enum ExitCode {
-    Success,
-    Failure(i32),
+/// Output of a running process, in the order it was read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProcessEvent<T> {
+    Stdout(T),
+    Stderr(T),
+    /// Always the last event
+    Exit(ExitCode),
 }
 
-pub struct WaitForProcess {
-    process: std::process::Child,
+/// Iterator over the output of a running process, ending with its `ProcessEvent::Exit`
+///
+/// A stdin set to `CreatePipe` is closed as soon as the process starts, so it reads end of input.
+/// Dropping the iterator before the end kills and reaps the process.
+pub struct ProcessStream<T> {
+    events: std::sync::mpsc::Receiver<ProcessEvent<T>>,
+    handle: ProcessHandle,
+    finished: bool,
 }
 
-impl Io for WaitForProcess {
-    type Output = ExitCode;
+impl<T> ProcessStream<T> {
+    pub fn handle(&self) -> ProcessHandle {
+        self.handle.clone()
+    }
+}
 
-    fn run(mut self) -> Self::Output {
-        let status = self.process.wait().unwrap();
+impl<T> Iterator for ProcessStream<T> {
+    type Item = ProcessEvent<T>;
 
-        if status.success() {
-            ExitCode::Success
-        } else {
-            ExitCode::Failure(status.code().unwrap())
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.events.recv() {
+            Ok(event) => Some(event),
+            // Both readers hit end of file
+            Err(_) => {
+                self.finished = true;
+                let status = self.handle.wait().ok()?;
+                Some(ProcessEvent::Exit(ExitCode::from(status)))
+            }
         }
     }
 }
 
-pub fn wait_for_process(process: std::process::Child) -> WaitForProcess {
-    WaitForProcess { process }
+impl<T> Drop for ProcessStream<T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.handle.kill();
+            let _ = self.handle.wait();
+        }
+    }
 }
 
-pub struct GetProcessExitCode {
-    process: std::process::Child,
+fn read_lines<R, F>(pipe: R, tx: std::sync::mpsc::Sender<ProcessEvent<String>>, tag: F)
+where
+    R: std::io::Read,
+    F: Fn(String) -> ProcessEvent<String>,
+{
+    use std::io::BufRead;
+
+    let mut reader = std::io::BufReader::new(pipe);
+    let mut line = Vec::new();
+
+    while let Ok(n) = reader.read_until(b'\n', &mut line) {
+        if n == 0 {
+            break;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        if tx
+            .send(tag(String::from_utf8_lossy(&line).into_owned()))
+            .is_err()
+        {
+            break;
+        }
+        line.clear();
+    }
 }
 
-impl Io for GetProcessExitCode {
-    type Output = Option<ExitCode>;
+fn read_chunks<R, F>(mut pipe: R, tx: std::sync::mpsc::Sender<ProcessEvent<Vec<u8>>>, tag: F)
+where
+    R: std::io::Read,
+    F: Fn(Vec<u8>) -> ProcessEvent<Vec<u8>>,
+{
+    let mut buf = [0; 8192];
 
-    fn run(mut self) -> Self::Output {
-        let status = self.process.try_wait().unwrap();
+    while let Ok(n) = pipe.read(&mut buf) {
+        if n == 0 || tx.send(tag(buf[..n].to_vec())).is_err() {
+            break;
+        }
+    }
+}
 
-        match status {
-            Some(status) => {
-                if status.success() {
-                    Some(ExitCode::Success)
-                } else {
-                    Some(ExitCode::Failure(status.code().unwrap()))
-                }
+fn stream_process<T, R>(process: CreateProcess, read: R) -> IoResult<ProcessStream<T>>
+where
+    T: Send + 'static,
+    R: Fn(
+            Box<dyn std::io::Read + Send>,
+            std::sync::mpsc::Sender<ProcessEvent<T>>,
+            fn(T) -> ProcessEvent<T>,
+        ) + Clone
+        + Send
+        + 'static,
+{
+    let mut child = current_runner().spawn(process)?;
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    // Nothing can write to a piped stdin, so close it rather than leave the process waiting on it
+    drop(child.stdin.take());
+
+    if let Some(stdout) = child.stdout.take() {
+        let (read, tx) = (read.clone(), tx.clone());
+        std::thread::spawn(move || read(Box::new(stdout), tx, ProcessEvent::Stdout));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        let (read, tx) = (read.clone(), tx.clone());
+        std::thread::spawn(move || read(Box::new(stderr), tx, ProcessEvent::Stderr));
+    }
+
+    Ok(ProcessStream {
+        events: rx,
+        handle: ProcessHandle::new(child),
+        finished: false,
+    })
+}
+
+/// Lines of stdout, interleaved with lines of stderr if it is set to `CreatePipe`
+///
+/// Invalid UTF-8 is replaced with U+FFFD. The exit code is available from `exit_code` once the
+/// iterator is exhausted.
+pub struct ProcessLines {
+    stream: ProcessStream<String>,
+    exit_code: Option<ExitCode>,
+}
+
+impl ProcessLines {
+    pub fn handle(&self) -> ProcessHandle {
+        self.stream.handle()
+    }
+
+    pub fn exit_code(&self) -> Option<ExitCode> {
+        self.exit_code
+    }
+}
+
+impl Iterator for ProcessLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stream.next()? {
+            ProcessEvent::Stdout(line) | ProcessEvent::Stderr(line) => Some(line),
+            ProcessEvent::Exit(code) => {
+                self.exit_code = Some(code);
+                None
             }
-            None => None,
         }
     }
 }
 
-pub fn get_process_exit_code(process: std::process::Child) -> GetProcessExitCode {
-    GetProcessExitCode { process }
+pub struct StreamProcessLinesIo {
+    process: CreateProcess,
 }
 
-pub struct TerminateProcess {
-    process: std::process::Child,
+impl Io for StreamProcessLinesIo {
+    type Output = ProcessLines;
+
+    fn run(self) -> Self::Output {
+        try_stream_process_lines(self.process).run().unwrap()
+    }
 }
 
-impl Io for TerminateProcess {
-    type Output = ();
+pub fn stream_process_lines(process: CreateProcess) -> StreamProcessLinesIo {
+    StreamProcessLinesIo { process }
+}
+
+pub struct TryStreamProcessLinesIo {
+    process: CreateProcess,
+}
+
+impl Io for TryStreamProcessLinesIo {
+    type Output = IoResult<ProcessLines>;
+
+    fn run(self) -> Self::Output {
+        let process = self.process.with_stdout(StdStream::CreatePipe);
 
-    fn run(mut self) -> Self::Output {
-        self.process.kill().unwrap();
+        Ok(ProcessLines {
+            stream: stream_process(process, read_lines)?,
+            exit_code: None,
+        })
     }
 }
 
-pub fn terminate_process(process: std::process::Child) -> TerminateProcess {
-    TerminateProcess { process }
+pub fn try_stream_process_lines(process: CreateProcess) -> TryStreamProcessLinesIo {
+    TryStreamProcessLinesIo { process }
 }
 
-// todo interruptProcessGroupOf
+pub struct StreamProcessTaggedLinesIo {
+    process: CreateProcess,
+}
 
-// Interprocess communication
+impl Io for StreamProcessTaggedLinesIo {
+    type Output = ProcessStream<String>;
+
+    fn run(self) -> Self::Output {
+        try_stream_process_tagged_lines(self.process).run().unwrap()
+    }
+}
+
+/// Lines of stdout and stderr tagged with their source, followed by the exit code
+pub fn stream_process_tagged_lines(process: CreateProcess) -> StreamProcessTaggedLinesIo {
+    StreamProcessTaggedLinesIo { process }
+}
+
+pub struct TryStreamProcessTaggedLinesIo {
+    process: CreateProcess,
+}
+
+impl Io for TryStreamProcessTaggedLinesIo {
+    type Output = IoResult<ProcessStream<String>>;
+
+    fn run(self) -> Self::Output {
+        let process = self
+            .process
+            .with_stdout(StdStream::CreatePipe)
+            .with_stderr(StdStream::CreatePipe);
+
+        stream_process(process, read_lines)
+    }
+}
+
+pub fn try_stream_process_tagged_lines(process: CreateProcess) -> TryStreamProcessTaggedLinesIo {
+    TryStreamProcessTaggedLinesIo { process }
+}
+
+pub struct StreamProcessChunksIo {
+    process: CreateProcess,
+}
+
+impl Io for StreamProcessChunksIo {
+    type Output = ProcessStream<Vec<u8>>;
+
+    fn run(self) -> Self::Output {
+        try_stream_process_chunks(self.process).run().unwrap()
+    }
+}
+
+/// Raw chunks of stdout and stderr as they arrive, followed by the exit code
+pub fn stream_process_chunks(process: CreateProcess) -> StreamProcessChunksIo {
+    StreamProcessChunksIo { process }
+}
+
+pub struct TryStreamProcessChunksIo {
+    process: CreateProcess,
+}
+
+impl Io for TryStreamProcessChunksIo {
+    type Output = IoResult<ProcessStream<Vec<u8>>>;
+
+    fn run(self) -> Self::Output {
+        let process = self
+            .process
+            .with_stdout(StdStream::CreatePipe)
+            .with_stderr(StdStream::CreatePipe);
+
+        stream_process(process, read_chunks)
+    }
+}
+
+pub fn try_stream_process_chunks(process: CreateProcess) -> TryStreamProcessChunksIo {
+    TryStreamProcessChunksIo { process }
+}
+
+// Children
+
+#[derive(Debug)]
+enum ChildProcess {
+    Os(std::process::Child),
+    Exited(std::process::ExitStatus),
+}
+
+/// A started process, as `std::process::Child`, which every `ProcessRunner` hands out
+///
+/// Besides real processes, a child can stand for one which has already exited with a given
+/// status, such as one scripted by `FakeProcessRunner`, whose pipes are then fed in-process. Such a
+/// child has the id 0, and signals don't reach it.
+#[derive(Debug)]
+pub struct Child {
+    pub stdin: Option<std::process::ChildStdin>,
+    pub stdout: Option<std::process::ChildStdout>,
+    pub stderr: Option<std::process::ChildStderr>,
+    process: ChildProcess,
+}
+
+impl Child {
+    /// A child which has already exited with `status`, with no pipes until they are set
+    pub fn exited(status: std::process::ExitStatus) -> Self {
+        Child {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            process: ChildProcess::Exited(status),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match &self.process {
+            ChildProcess::Os(child) => child.id(),
+            ChildProcess::Exited(_) => 0,
+        }
+    }
+
+    /// The status of a child which never ran as a real process
+    fn exited_status(&self) -> Option<std::process::ExitStatus> {
+        match self.process {
+            ChildProcess::Os(_) => None,
+            ChildProcess::Exited(status) => Some(status),
+        }
+    }
+
+    pub fn try_wait(&mut self) -> std::io::Result<Option<std::process::ExitStatus>> {
+        match &mut self.process {
+            ChildProcess::Os(child) => child.try_wait(),
+            ChildProcess::Exited(status) => Ok(Some(*status)),
+        }
+    }
+
+    /// Block until the child exits, closing its stdin first
+    pub fn wait(&mut self) -> std::io::Result<std::process::ExitStatus> {
+        drop(self.stdin.take());
+
+        match &mut self.process {
+            ChildProcess::Os(child) => child.wait(),
+            ChildProcess::Exited(status) => Ok(*status),
+        }
+    }
+
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        match &mut self.process {
+            ChildProcess::Os(child) => child.kill(),
+            ChildProcess::Exited(_) => Ok(()),
+        }
+    }
+}
+
+impl From<std::process::Child> for Child {
+    fn from(mut child: std::process::Child) -> Self {
+        Child {
+            stdin: child.stdin.take(),
+            stdout: child.stdout.take(),
+            stderr: child.stderr.take(),
+            process: ChildProcess::Os(child),
+        }
+    }
+}
+
+// Scoped processes
+
+/// Shared reference to a child process
+///
+/// Clones refer to the same process, so it can be polled, signalled and waited on from several
+/// places. Every query takes the handle instead of consuming a `Child`.
+#[derive(Clone)]
+pub struct ProcessHandle {
+    state: Arc<Mutex<ChildState>>,
+}
+
+#[derive(Clone, Copy)]
+struct Exited {
+    status: std::process::ExitStatus,
+    #[cfg(unix)]
+    usage: ResourceUsage,
+}
+
+/// The child and, once reaped, how it exited
+///
+/// On Unix the child is reaped with `wait4` here instead of through `std::process::Child`, so its
+/// resource usage is kept.
+struct ChildState {
+    child: Child,
+    exited: Option<Exited>,
+    /// The process group the child leads, recorded at spawn so it can still be signalled once the
+    /// leader is gone
+    #[cfg(unix)]
+    group: Option<libc::pid_t>,
+}
+
+impl ChildState {
+    fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    #[cfg(unix)]
+    fn reap(&mut self, flags: libc::c_int) -> std::io::Result<Option<Exited>> {
+        use std::os::unix::process::ExitStatusExt;
+
+        if self.exited.is_some() {
+            return Ok(self.exited);
+        }
+
+        let mut status = 0;
+        // SAFETY: rusage is plain data, for which all zeroes is valid
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: both out pointers are valid, and only this state reaps the child
+            match unsafe { libc::wait4(self.id() as libc::pid_t, &mut status, flags, &mut usage) } {
+                -1 => {
+                    let error = std::io::Error::last_os_error();
+                    match error.raw_os_error() {
+                        Some(libc::EINTR) => continue,
+                        // Already reaped through the `Child` before it was given to the handle
+                        Some(libc::ECHILD) => {
+                            let status = self.child.try_wait()?.ok_or(error)?;
+                            self.exited = Some(Exited {
+                                status,
+                                usage: ResourceUsage::default(),
+                            });
+                            return Ok(self.exited);
+                        }
+                        _ => return Err(error),
+                    }
+                }
+                0 => return Ok(None),
+                _ => break,
+            }
+        }
+
+        self.exited = Some(Exited {
+            status: std::process::ExitStatus::from_raw(status),
+            usage: ResourceUsage::from(usage),
+        });
+        Ok(self.exited)
+    }
+
+    fn try_wait(&mut self) -> std::io::Result<Option<Exited>> {
+        #[cfg(unix)]
+        return self.reap(libc::WNOHANG);
+
+        #[cfg(not(unix))]
+        {
+            if self.exited.is_none() {
+                self.exited = self.child.try_wait()?.map(|status| Exited { status });
+            }
+            Ok(self.exited)
+        }
+    }
+
+    /// Block until the child exits, closing its stdin first as `std::process::Child::wait` does
+    fn wait(&mut self) -> std::io::Result<Exited> {
+        drop(self.child.stdin.take());
+
+        #[cfg(unix)]
+        return Ok(self.reap(0)?.expect("wait4 returned without a status"));
+
+        #[cfg(not(unix))]
+        {
+            let status = self.child.wait()?;
+            Ok(*self.exited.get_or_insert(Exited { status }))
+        }
+    }
+
+    fn kill(&mut self) -> std::io::Result<()> {
+        // Once reaped the pid may belong to another process
+        if self.exited.is_some() {
+            return Ok(());
+        }
+
+        self.child.kill()
+    }
+}
+
+impl ProcessHandle {
+    pub fn new(child: Child) -> Self {
+        let exited = child.exited_status().map(|status| Exited {
+            status,
+            #[cfg(unix)]
+            usage: ResourceUsage::default(),
+        });
+
+        #[cfg(unix)]
+        let group = match exited {
+            Some(_) => None,
+            None => {
+                let pid = child.id() as libc::pid_t;
+                // SAFETY: getpgid has no memory effects, and the unreaped child still owns its pid
+                (unsafe { libc::getpgid(pid) } == pid).then_some(pid)
+            }
+        };
+
+        ProcessHandle {
+            state: Arc::new(Mutex::new(ChildState {
+                child,
+                exited,
+                #[cfg(unix)]
+                group,
+            })),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.state.lock().unwrap().id()
+    }
+
+    fn try_wait(&self) -> std::io::Result<Option<std::process::ExitStatus>> {
+        let exited = self.state.lock().unwrap().try_wait()?;
+        Ok(exited.map(|exited| exited.status))
+    }
+
+    fn kill(&self) -> std::io::Result<()> {
+        self.state.lock().unwrap().kill()
+    }
+
+    fn wait(&self) -> std::io::Result<std::process::ExitStatus> {
+        Ok(self.wait_exited()?.status)
+    }
+
+    /// Block until the process exits without holding the lock meanwhile, so other clones can
+    /// still signal it
+    ///
+    /// The wait polls, so a thread killed with `kill_thread` (or timed out) kills the process and
+    /// reaps it instead of blocking on it forever.
+    fn wait_exited(&self) -> std::io::Result<Exited> {
+        drop(self.state.lock().unwrap().child.stdin.take());
+
+        let mut poll = std::time::Duration::from_millis(1);
+        while self.state.lock().unwrap().try_wait()?.is_none() {
+            if current_thread_killed() {
+                self.kill()?;
+                break;
+            }
+            std::thread::sleep(poll);
+            poll = (poll * 2).min(CANCEL_POLL);
+        }
+
+        self.state.lock().unwrap().wait()
+    }
+}
+
+impl From<Child> for ProcessHandle {
+    fn from(child: Child) -> Self {
+        ProcessHandle::new(child)
+    }
+}
+
+impl From<std::process::Child> for ProcessHandle {
+    fn from(child: std::process::Child) -> Self {
+        ProcessHandle::new(child.into())
+    }
+}
+
+pub type ProcessPipes = (
+    Option<std::process::ChildStdin>,
+    Option<std::process::ChildStdout>,
+    Option<std::process::ChildStderr>,
+    ProcessHandle,
+);
+
+pub struct WithCreateProcessIo<F> {
+    process: CreateProcess,
+    f: F,
+}
+
+impl<F, I> Io for WithCreateProcessIo<F>
+where
+    F: FnOnce(
+        Option<std::process::ChildStdin>,
+        Option<std::process::ChildStdout>,
+        Option<std::process::ChildStderr>,
+        ProcessHandle,
+    ) -> I,
+    I: Io,
+{
+    type Output = I::Output;
+
+    fn run(self) -> Self::Output {
+        try_with_create_process(self.process, self.f).run().unwrap()
+    }
+}
+
+/// Start a process, hand its pipes and handle to `f`, and clean it up once `f`'s action is done.
+///
+/// The child is killed if still running and always reaped, even if the action panics.
+pub fn with_create_process<F, I>(process: CreateProcess, f: F) -> WithCreateProcessIo<F>
+where
+    F: FnOnce(
+        Option<std::process::ChildStdin>,
+        Option<std::process::ChildStdout>,
+        Option<std::process::ChildStderr>,
+        ProcessHandle,
+    ) -> I,
+    I: Io,
+{
+    WithCreateProcessIo { process, f }
+}
+
+pub struct TryWithCreateProcessIo<F> {
+    process: CreateProcess,
+    f: F,
+}
+
+impl<F, I> Io for TryWithCreateProcessIo<F>
+where
+    F: FnOnce(
+        Option<std::process::ChildStdin>,
+        Option<std::process::ChildStdout>,
+        Option<std::process::ChildStderr>,
+        ProcessHandle,
+    ) -> I,
+    I: Io,
+{
+    type Output = IoResult<I::Output>;
+
+    fn run(self) -> Self::Output {
+        let mut child = try_create_process(self.process).run()?;
+        let (stdin, stdout, stderr) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take());
+        let handle = ProcessHandle::new(child);
+
+        let (f, used) = (self.f, handle.clone());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(stdin, stdout, stderr, used).run()));
+        cleanup_process((None, None, None, handle)).run();
+
+        match result {
+            Ok(output) => Ok(output),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+pub fn try_with_create_process<F, I>(process: CreateProcess, f: F) -> TryWithCreateProcessIo<F>
+where
+    F: FnOnce(
+        Option<std::process::ChildStdin>,
+        Option<std::process::ChildStdout>,
+        Option<std::process::ChildStderr>,
+        ProcessHandle,
+    ) -> I,
+    I: Io,
+{
+    TryWithCreateProcessIo { process, f }
+}
+
+pub struct CleanupProcessIo {
+    pipes: ProcessPipes,
+}
+
+impl Io for CleanupProcessIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        let (stdin, stdout, stderr, handle) = self.pipes;
+        drop(stdin);
+        drop(stdout);
+        drop(stderr);
+
+        // Errors are ignored; the process may have already exited or been reaped
+        let mut state = handle.state.lock().unwrap();
+        if let Ok(None) = state.try_wait() {
+            let _ = state.kill();
+        }
+        let _ = state.wait();
+    }
+}
+
+/// Close the pipes, kill the process if it is still running, and reap it.
+pub fn cleanup_process(pipes: ProcessPipes) -> CleanupProcessIo {
+    CleanupProcessIo { pipes }
+}
+
+// Related utilities
+
+/// Render a command for logs and error messages, quoting arguments so it can be pasted into a shell
+pub fn show_command_for_user(exec: PathBuf, args: Vec<String>) -> String {
+    let exec = exec.to_string_lossy().into_owned();
+
+    sh::join(std::iter::once(exec).chain(args))
+}
+
+pub struct GetPid {
+    handle: ProcessHandle,
+}
+
+impl Io for GetPid {
+    type Output = u32;
+
+    fn run(self) -> Self::Output {
+        self.handle.id()
+    }
+}
+
+pub fn get_pid<P: Into<ProcessHandle>>(process: P) -> GetPid {
+    GetPid {
+        handle: process.into(),
+    }
+}
+
+#[derive(Clone)]
+pub struct GetCurrentPid;
+
+impl Io for GetCurrentPid {
+    type Output = u32;
+
+    fn run(self) -> Self::Output {
+        std::process::id()
+    }
+}
+
+pub fn get_current_pid() -> GetCurrentPid {
+    GetCurrentPid
+}
+
+// Process completion
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExitCode {
+    Success,
+    Failure(i32),
+    /// Terminated by a signal, and whether it dumped core
+    Signaled(i32, bool),
+}
+
+impl ExitCode {
+    pub fn is_success(&self) -> bool {
+        *self == ExitCode::Success
+    }
+
+    /// The byte a parent sees as the exit status, `128 + signal` for signals
+    ///
+    /// A failure which would truncate to 0, like `Failure(256)`, reports 1 so it never reads as
+    /// success.
+    pub(crate) fn status_byte(self) -> u8 {
+        let code = match self {
+            ExitCode::Success => return 0,
+            ExitCode::Failure(code) => code,
+            ExitCode::Signaled(signal, _) => 128 + signal,
+        };
+
+        match (code & 0xff) as u8 {
+            0 => 1,
+            byte => byte,
+        }
+    }
+}
+
+impl From<std::process::ExitStatus> for ExitCode {
+    fn from(status: std::process::ExitStatus) -> Self {
+        if status.success() {
+            return ExitCode::Success;
+        }
+
+        if let Some(code) = status.code() {
+            return ExitCode::Failure(code);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+
+            match status.signal() {
+                Some(signal) => ExitCode::Signaled(signal, status.core_dumped()),
+                None => ExitCode::Failure(status.into_raw()),
+            }
+        }
+
+        #[cfg(not(unix))]
+        unreachable!("exit status without a code")
+    }
+}
+
+#[cfg(unix)]
+impl From<ExitCode> for std::process::ExitStatus {
+    fn from(code: ExitCode) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Raw status as returned by waitpid
+        std::process::ExitStatus::from_raw(match code {
+            ExitCode::Success => 0,
+            ExitCode::Failure(_) => i32::from(code.status_byte()) << 8,
+            ExitCode::Signaled(signal, core_dumped) => signal | if core_dumped { 0x80 } else { 0 },
+        })
+    }
+}
+
+#[cfg(windows)]
+impl From<ExitCode> for std::process::ExitStatus {
+    fn from(code: ExitCode) -> Self {
+        use std::os::windows::process::ExitStatusExt;
+
+        std::process::ExitStatus::from_raw(match code {
+            ExitCode::Success => 0,
+            ExitCode::Failure(code) => code as u32,
+            ExitCode::Signaled(_, _) => 1,
+        })
+    }
+}
+
+/// Lets a program exit with a child's status, using the shell's `128 + signal` for signals
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> Self {
+        std::process::ExitCode::from(code.status_byte())
+    }
+}
+
+pub struct WaitForProcess {
+    handle: ProcessHandle,
+}
+
+impl Io for WaitForProcess {
+    type Output = ExitCode;
+
+    fn run(self) -> Self::Output {
+        try_wait_for_process(self.handle).run().unwrap()
+    }
+}
+
+/// Wait for the process to exit
+///
+/// If the waiting thread is killed with `kill_thread` or runs out of `timeout`, the process is
+/// killed and reaped instead.
+pub fn wait_for_process<P: Into<ProcessHandle>>(process: P) -> WaitForProcess {
+    WaitForProcess {
+        handle: process.into(),
+    }
+}
+
+pub struct TryWaitForProcess {
+    handle: ProcessHandle,
+}
+
+impl Io for TryWaitForProcess {
+    type Output = IoResult<ExitCode>;
+
+    fn run(self) -> Self::Output {
+        Ok(ExitCode::from(self.handle.wait()?))
+    }
+}
+
+pub fn try_wait_for_process<P: Into<ProcessHandle>>(process: P) -> TryWaitForProcess {
+    TryWaitForProcess {
+        handle: process.into(),
+    }
+}
+
+/// Resources used by a child process, as reported by `wait4`
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub user_time: std::time::Duration,
+    pub system_time: std::time::Duration,
+    /// Peak resident set size in bytes
+    pub max_rss: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    pub voluntary_context_switches: u64,
+    pub involuntary_context_switches: u64,
+}
+
+#[cfg(unix)]
+impl From<libc::rusage> for ResourceUsage {
+    fn from(usage: libc::rusage) -> Self {
+        let duration = |time: libc::timeval| {
+            std::time::Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000)
+        };
+
+        // Apple reports bytes, everyone else kilobytes
+        let rss_unit = if cfg!(target_vendor = "apple") {
+            1
+        } else {
+            1024
+        };
+
+        ResourceUsage {
+            user_time: duration(usage.ru_utime),
+            system_time: duration(usage.ru_stime),
+            max_rss: usage.ru_maxrss as u64 * rss_unit,
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+            voluntary_context_switches: usage.ru_nvcsw as u64,
+            involuntary_context_switches: usage.ru_nivcsw as u64,
+        }
+    }
+}
+
+#[cfg(unix)]
+pub struct WaitForProcessWithUsage {
+    handle: ProcessHandle,
+}
+
+#[cfg(unix)]
+impl Io for WaitForProcessWithUsage {
+    type Output = (ExitCode, ResourceUsage);
+
+    fn run(self) -> Self::Output {
+        try_wait_for_process_with_usage(self.handle).run().unwrap()
+    }
+}
+
+#[cfg(unix)]
+pub fn wait_for_process_with_usage<P: Into<ProcessHandle>>(process: P) -> WaitForProcessWithUsage {
+    WaitForProcessWithUsage {
+        handle: process.into(),
+    }
+}
+
+#[cfg(unix)]
+pub struct TryWaitForProcessWithUsage {
+    handle: ProcessHandle,
+}
+
+#[cfg(unix)]
+impl Io for TryWaitForProcessWithUsage {
+    type Output = IoResult<(ExitCode, ResourceUsage)>;
+
+    fn run(self) -> Self::Output {
+        let exited = self.handle.wait_exited()?;
+        Ok((ExitCode::from(exited.status), exited.usage))
+    }
+}
+
+/// Wait for the process and report the resources it used alongside its exit code
+///
+/// Any clone of the handle can ask; the usage is kept once the process is reaped.
+#[cfg(unix)]
+pub fn try_wait_for_process_with_usage<P: Into<ProcessHandle>>(
+    process: P,
+) -> TryWaitForProcessWithUsage {
+    TryWaitForProcessWithUsage {
+        handle: process.into(),
+    }
+}
+
+pub struct GetProcessExitCode {
+    handle: ProcessHandle,
+}
+
+impl Io for GetProcessExitCode {
+    type Output = Option<ExitCode>;
+
+    fn run(self) -> Self::Output {
+        try_get_process_exit_code(self.handle).run().unwrap()
+    }
+}
+
+pub fn get_process_exit_code<P: Into<ProcessHandle>>(process: P) -> GetProcessExitCode {
+    GetProcessExitCode {
+        handle: process.into(),
+    }
+}
+
+pub struct TryGetProcessExitCode {
+    handle: ProcessHandle,
+}
+
+impl Io for TryGetProcessExitCode {
+    type Output = IoResult<Option<ExitCode>>;
+
+    fn run(self) -> Self::Output {
+        Ok(self.handle.try_wait()?.map(ExitCode::from))
+    }
+}
+
+pub fn try_get_process_exit_code<P: Into<ProcessHandle>>(process: P) -> TryGetProcessExitCode {
+    TryGetProcessExitCode {
+        handle: process.into(),
+    }
+}
+
+pub struct TerminateProcess {
+    handle: ProcessHandle,
+}
+
+impl Io for TerminateProcess {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_terminate_process(self.handle).run().unwrap()
+    }
+}
+
+pub fn terminate_process<P: Into<ProcessHandle>>(process: P) -> TerminateProcess {
+    TerminateProcess {
+        handle: process.into(),
+    }
+}
+
+pub struct TryTerminateProcess {
+    handle: ProcessHandle,
+}
+
+impl Io for TryTerminateProcess {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(self.handle.kill()?)
+    }
+}
+
+pub fn try_terminate_process<P: Into<ProcessHandle>>(process: P) -> TryTerminateProcess {
+    TryTerminateProcess {
+        handle: process.into(),
+    }
+}
+
+// Signals
+
+/// Unix signals which can be delivered to a child process
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    Hup,
+    Int,
+    Quit,
+    Kill,
+    Term,
+    Usr1,
+    Usr2,
+    Stop,
+    Cont,
+    Other(i32),
+}
+
+#[cfg(unix)]
+impl Signal {
+    pub fn as_raw(self) -> i32 {
+        match self {
+            Signal::Hup => libc::SIGHUP,
+            Signal::Int => libc::SIGINT,
+            Signal::Quit => libc::SIGQUIT,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Term => libc::SIGTERM,
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Usr2 => libc::SIGUSR2,
+            Signal::Stop => libc::SIGSTOP,
+            Signal::Cont => libc::SIGCONT,
+            Signal::Other(signal) => signal,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn check_os(result: libc::c_int) -> IoResult<()> {
+    if result == -1 {
+        Err(std::io::Error::last_os_error().into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub struct SignalProcessIo {
+    handle: ProcessHandle,
+    signal: Signal,
+}
+
+#[cfg(unix)]
+impl Io for SignalProcessIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_signal_process(self.handle, self.signal).run().unwrap()
+    }
+}
+
+#[cfg(unix)]
+pub fn signal_process<P: Into<ProcessHandle>>(handle: P, signal: Signal) -> SignalProcessIo {
+    SignalProcessIo {
+        handle: handle.into(),
+        signal,
+    }
+}
+
+#[cfg(unix)]
+pub struct TrySignalProcessIo {
+    handle: ProcessHandle,
+    signal: Signal,
+}
+
+#[cfg(unix)]
+impl Io for TrySignalProcessIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        let mut state = self.handle.state.lock().unwrap();
+
+        // Once reaped the pid may belong to another process
+        if state.try_wait()?.is_some() {
+            return Ok(());
+        }
+
+        // SAFETY: kill has no memory effects, and the unreaped child still owns its pid
+        check_os(unsafe { libc::kill(state.id() as libc::pid_t, self.signal.as_raw()) })
+    }
+}
+
+/// Send `signal` to the process, doing nothing if it has already exited
+#[cfg(unix)]
+pub fn try_signal_process<P: Into<ProcessHandle>>(handle: P, signal: Signal) -> TrySignalProcessIo {
+    TrySignalProcessIo {
+        handle: handle.into(),
+        signal,
+    }
+}
+
+#[cfg(unix)]
+pub struct SignalProcessGroupOfIo {
+    handle: ProcessHandle,
+    signal: Signal,
+}
+
+#[cfg(unix)]
+impl Io for SignalProcessGroupOfIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_signal_process_group_of(self.handle, self.signal)
+            .run()
+            .unwrap()
+    }
+}
+
+#[cfg(unix)]
+pub fn signal_process_group_of<P: Into<ProcessHandle>>(
+    handle: P,
+    signal: Signal,
+) -> SignalProcessGroupOfIo {
+    SignalProcessGroupOfIo {
+        handle: handle.into(),
+        signal,
+    }
+}
+
+#[cfg(unix)]
+pub struct TrySignalProcessGroupOfIo {
+    handle: ProcessHandle,
+    signal: Signal,
+}
+
+#[cfg(unix)]
+impl Io for TrySignalProcessGroupOfIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        let mut state = self.handle.state.lock().unwrap();
+
+        let pgid = match state.group {
+            Some(pgid) => pgid,
+            None => {
+                // Once reaped the pid may belong to another process
+                if state.try_wait()?.is_some() {
+                    return Ok(());
+                }
+
+                // SAFETY: getpgid has no memory effects, and the unreaped child still owns its pid
+                let pgid = unsafe { libc::getpgid(state.id() as libc::pid_t) };
+                check_os(pgid)?;
+                pgid
+            }
+        };
+
+        // SAFETY: killpg has no memory effects
+        match check_os(unsafe { libc::killpg(pgid, self.signal.as_raw()) }) {
+            // Every process in the group has exited
+            Err(Error::Io(e)) if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Send `signal` to every process in the process group of the given process
+///
+/// Start the child with `CreateProcess::create_group` or `new_session` so this doesn't reach the
+/// caller's own group. The group is then the one recorded when the child was spawned, so it is
+/// still signalled after the child itself has exited. Otherwise the child's current group is
+/// signalled, and nothing happens once the child has exited.
+#[cfg(unix)]
+pub fn try_signal_process_group_of<P: Into<ProcessHandle>>(
+    handle: P,
+    signal: Signal,
+) -> TrySignalProcessGroupOfIo {
+    TrySignalProcessGroupOfIo {
+        handle: handle.into(),
+        signal,
+    }
+}
+
+#[cfg(unix)]
+pub fn interrupt_process_group_of<P: Into<ProcessHandle>>(handle: P) -> SignalProcessGroupOfIo {
+    signal_process_group_of(handle, Signal::Int)
+}
+
+#[cfg(unix)]
+pub fn try_interrupt_process_group_of<P: Into<ProcessHandle>>(
+    handle: P,
+) -> TrySignalProcessGroupOfIo {
+    try_signal_process_group_of(handle, Signal::Int)
+}
+
+pub struct TerminateProcessGracefullyIo {
+    handle: ProcessHandle,
+    grace: std::time::Duration,
+}
+
+impl Io for TerminateProcessGracefullyIo {
+    type Output = std::process::ExitStatus;
+
+    fn run(self) -> Self::Output {
+        try_terminate_process_gracefully(self.handle, self.grace)
+            .run()
+            .unwrap()
+    }
+}
+
+pub fn terminate_process_gracefully<P: Into<ProcessHandle>>(
+    handle: P,
+    grace: std::time::Duration,
+) -> TerminateProcessGracefullyIo {
+    TerminateProcessGracefullyIo {
+        handle: handle.into(),
+        grace,
+    }
+}
+
+pub struct TryTerminateProcessGracefullyIo {
+    handle: ProcessHandle,
+    grace: std::time::Duration,
+}
+
+impl Io for TryTerminateProcessGracefullyIo {
+    type Output = IoResult<std::process::ExitStatus>;
+
+    fn run(self) -> Self::Output {
+        {
+            let mut state = self.handle.state.lock().unwrap();
+
+            if let Some(exited) = state.try_wait()? {
+                return Ok(exited.status);
+            }
+
+            // SAFETY: kill has no memory effects, and the unreaped child still owns its pid
+            #[cfg(unix)]
+            check_os(unsafe { libc::kill(state.id() as libc::pid_t, libc::SIGTERM) })?;
+        }
+
+        // Unlocked between polls, so other clones of the handle stay usable during the grace
+        #[cfg(unix)]
+        {
+            let deadline = std::time::Instant::now() + self.grace;
+            while std::time::Instant::now() < deadline {
+                if let Some(status) = self.handle.try_wait()? {
+                    return Ok(status);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = self.grace;
+
+        self.handle.kill()?;
+        Ok(self.handle.wait()?)
+    }
+}
+
+/// Ask the process to stop with SIGTERM, then kill it if it is still running after `grace`
+///
+/// Without Unix signals the process is killed straight away.
+pub fn try_terminate_process_gracefully<P: Into<ProcessHandle>>(
+    handle: P,
+    grace: std::time::Duration,
+) -> TryTerminateProcessGracefullyIo {
+    TryTerminateProcessGracefullyIo {
+        handle: handle.into(),
+        grace,
+    }
+}
+
+// Interprocess communication
+
+pub struct CreatePipeIo;
+
+impl Io for CreatePipeIo {
+    type Output = (std::io::PipeReader, std::io::PipeWriter);
+
+    fn run(self) -> Self::Output {
+        try_create_pipe().run().unwrap()
+    }
+}
+
+/// Anonymous pipe whose ends can be passed to `StdStream::UseHandle` with `.into()`
+pub fn create_pipe() -> CreatePipeIo {
+    CreatePipeIo
+}
+
+pub struct TryCreatePipeIo;
+
+impl Io for TryCreatePipeIo {
+    type Output = IoResult<(std::io::PipeReader, std::io::PipeWriter)>;
+
+    fn run(self) -> Self::Output {
+        Ok(std::io::pipe()?)
+    }
+}
+
+pub fn try_create_pipe() -> TryCreatePipeIo {
+    TryCreatePipeIo
+}
+
+pub struct CreatePipelineIo {
+    processes: Vec<CreateProcess>,
+}
+
+impl Io for CreatePipelineIo {
+    type Output = Vec<Child>;
+
+    fn run(self) -> Self::Output {
+        try_create_pipeline(self.processes).run().unwrap()
+    }
+}
+
+/// Start the processes with the stdout of each connected to the stdin of the next, like `a | b | c`
+///
+/// The first stdin and the last stdout are left as configured.
+pub fn create_pipeline(processes: Vec<CreateProcess>) -> CreatePipelineIo {
+    CreatePipelineIo { processes }
+}
+
+pub struct TryCreatePipelineIo {
+    processes: Vec<CreateProcess>,
+}
+
+impl Io for TryCreatePipelineIo {
+    type Output = IoResult<Vec<Child>>;
+
+    fn run(self) -> Self::Output {
+        let last = self.processes.len().saturating_sub(1);
+        let mut children: Vec<Child> = Vec::new();
+        let mut prev_stdout: Option<std::process::ChildStdout> = None;
+
+        for (i, mut process) in self.processes.into_iter().enumerate() {
+            if let Some(stdout) = prev_stdout.take() {
+                process = process.with_stdin(StdStream::UseHandle(stdout.into()));
+            }
+            if i < last {
+                process = process.with_stdout(StdStream::CreatePipe);
+            }
+
+            match current_runner().spawn(process) {
+                Ok(mut child) => {
+                    if i < last {
+                        prev_stdout = child.stdout.take();
+                    }
+                    children.push(child);
+                }
+                Err(e) => {
+                    for mut child in children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(children)
+    }
+}
+
+pub fn try_create_pipeline(processes: Vec<CreateProcess>) -> TryCreatePipelineIo {
+    TryCreatePipelineIo { processes }
+}
+
+pub struct ReadPipelineIo {
+    processes: Vec<CreateProcess>,
+    stdin: String,
+}
+
+impl Io for ReadPipelineIo {
+    type Output = String;
+
+    fn run(self) -> Self::Output {
+        try_read_pipeline(self.processes, self.stdin).run().unwrap()
+    }
+}
+
+/// Run a pipeline to completion, feeding `stdin` to the first process and reading the last's stdout
+pub fn read_pipeline(processes: Vec<CreateProcess>, stdin: String) -> ReadPipelineIo {
+    ReadPipelineIo { processes, stdin }
+}
+
+pub struct TryReadPipelineIo {
+    processes: Vec<CreateProcess>,
+    stdin: String,
+}
+
+impl Io for TryReadPipelineIo {
+    type Output = IoResult<String>;
+
+    fn run(self) -> Self::Output {
+        let mut processes = self.processes;
+        let Some(last) = processes.pop() else {
+            return Ok(String::new());
+        };
+        processes.push(last.with_stdout(StdStream::CreatePipe));
+        let first = processes.remove(0);
+        processes.insert(0, first.with_stdin(StdStream::CreatePipe));
+
+        let mut children = try_create_pipeline(processes).run()?;
+        let writer = write_stdin(children[0].stdin.take(), self.stdin.into_bytes());
+
+        let last = children.pop().unwrap();
+        let output = communicate(last, Vec::new());
+        if output.is_err() {
+            for child in &mut children {
+                let _ = child.kill();
+            }
+        }
+
+        // Reap every child before reporting any error, so none is left running
+        let waited: Vec<_> = children
+            .into_iter()
+            .map(|child| ProcessHandle::new(child).wait())
+            .collect();
+        let output = output?;
+        for status in waited {
+            status?;
+        }
+        join_stdin(writer)?;
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+pub fn try_read_pipeline(processes: Vec<CreateProcess>, stdin: String) -> TryReadPipelineIo {
+    TryReadPipelineIo { processes, stdin }
+}
+
+#[cfg(unix)]
+pub struct CreateNamedPipeIo {
+    path: PathBuf,
+    mode: u32,
+}
+
+#[cfg(unix)]
+impl Io for CreateNamedPipeIo {
+    type Output = ();
+
+    fn run(self) -> Self::Output {
+        try_create_named_pipe(self.path, self.mode).run().unwrap()
+    }
+}
+
+/// Create a FIFO at `path` with the given permission bits
+#[cfg(unix)]
+pub fn create_named_pipe(path: PathBuf, mode: u32) -> CreateNamedPipeIo {
+    CreateNamedPipeIo { path, mode }
+}
+
+#[cfg(unix)]
+pub struct TryCreateNamedPipeIo {
+    path: PathBuf,
+    mode: u32,
+}
+
+#[cfg(unix)]
+impl Io for TryCreateNamedPipeIo {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::ffi::CString::new(self.path.as_os_str().as_bytes())
+            .map_err(std::io::Error::from)?;
+
+        // SAFETY: `path` is a NUL-terminated string which outlives the call
+        check_os(unsafe { libc::mkfifo(path.as_ptr(), self.mode as libc::mode_t) })
+    }
+}
+
+#[cfg(unix)]
+pub fn try_create_named_pipe(path: PathBuf, mode: u32) -> TryCreateNamedPipeIo {
+    TryCreateNamedPipeIo { path, mode }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::IoPure;
+
+    #[test]
+    fn test_try_process_actions_report_errors() {
+        let missing = PathBuf::from("entoli-no-such-program");
+
+        assert!(matches!(
+            try_call_command("exit 3".to_string()).run(),
+            Err(Error::ProcessFailed(status)) if status.code() == Some(3)
+        ));
+        assert!(matches!(
+            try_call_process(missing.clone(), vec![]).run(),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+        assert!(try_read_process(missing, vec![], String::new())
+            .run()
+            .is_err());
+        assert!(
+            try_read_process("printf".into(), vec!["\\377".into()], String::new())
+                .run()
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout_kills_waited_process() {
+        use crate::control::concurrent::timeout;
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let handle =
+            ProcessHandle::new(create_process(proc("sleep".into(), vec!["10".into()])).run());
+
+        assert_eq!(
+            timeout(Duration::from_millis(100), wait_for_process(handle.clone())).run(),
+            None
+        );
+        assert_eq!(
+            wait_for_process(handle).run(),
+            ExitCode::Signaled(libc::SIGKILL, false)
+        );
+        assert_eq!(
+            timeout(
+                Duration::from_millis(100),
+                try_call_command("sleep 10".to_string())
+            )
+            .run()
+            .map(|result| result.is_ok()),
+            None
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_with_create_process_pipes() {
+        use std::io::Read;
+
+        let process = proc("cat".into(), vec![])
+            .with_stdin(StdStream::CreatePipe)
+            .with_stdout(StdStream::CreatePipe);
+
+        let output = with_create_process(process, |stdin, stdout, _, handle| {
+            let mut stdin = stdin.unwrap();
+            stdin.write_all(b"hello").unwrap();
+            drop(stdin);
+
+            let mut output = String::new();
+            stdout.unwrap().read_to_string(&mut output).unwrap();
+            wait_for_process(handle).map(move |code| (code, output))
+        })
+        .run();
+
+        assert_eq!(output, (ExitCode::Success, "hello".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_with_create_process_kills_on_panic() {
+        let mut handle = None;
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_create_process(proc("sleep".into(), vec!["10".into()]), |_, _, _, h| {
+                handle = Some(h);
+                IoPure::<()>::pure(()).map(|_| -> () { panic!("boom") })
+            })
+            .run()
+        }));
+
+        assert!(panicked.is_err());
+        assert_eq!(
+            get_process_exit_code(handle.unwrap()).run(),
+            Some(ExitCode::Signaled(libc::SIGKILL, false))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cleanup_process() {
+        let mut child = create_process(
+            proc("sleep".into(), vec!["10".into()]).with_stdin(StdStream::CreatePipe),
+        )
+        .run();
+        let stdin = child.stdin.take();
+        let handle = ProcessHandle::new(child);
+
+        cleanup_process((stdin, None, None, handle.clone())).run();
+
+        assert_eq!(
+            get_process_exit_code(handle.clone()).run(),
+            Some(ExitCode::Signaled(libc::SIGKILL, false))
+        );
+        // Cleaning up a reaped process does nothing
+        cleanup_process((None, None, None, handle)).run();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_process_builder() {
+        // Reads the inherited HOME
+        let _env = crate::system::environment::ENV_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let read = |process: CreateProcess| {
+            read_create_process(process.with_stdout(StdStream::CreatePipe), String::new()).run()
+        };
+
+        assert_eq!(read(shell("pwd").with_cwd("/")), "/\n");
+        assert_eq!(
+            read(shell("echo $ENTOLI_A ${HOME:+home}").add_env("ENTOLI_A", "1")),
+            "1 home\n"
+        );
+        assert_eq!(
+            read(
+                shell("echo $ENTOLI_A ${HOME:-none}")
+                    .with_env(vec![("ENTOLI_A".into(), "2".into())])
+            ),
+            "2 none\n"
+        );
+        assert_eq!(
+            read(shell("echo ${HOME:-none}").add_env("HOME", "x").clear_env()),
+            "none\n"
+        );
+        assert_eq!(read(shell("umask").umask(0o027)), "0027\n");
+        assert_eq!(read(shell("cat").with_stdin(StdStream::NoStream)), "");
+    }
+
+    #[test]
+    fn test_read_create_process_with_output() {
+        // Larger than any pipe buffer, so writing it all before reading would deadlock
+        let input: Vec<u8> = (0..4 << 20).map(|i| (i % 251) as u8).collect();
+        let echoed =
+            read_create_process_with_output(proc("cat".into(), vec![]), input.clone()).run();
+
+        assert_eq!(echoed.exit_code, ExitCode::Success);
+        assert!(echoed.stdout == input);
+
+        let output = read_create_process_with_output(
+            shell("printf out; printf 'err\\377' >&2; exit 4"),
+            vec![],
+        )
+        .run();
+
+        assert_eq!(output.exit_code, ExitCode::Failure(4));
+        assert_eq!(output.stdout_string().unwrap(), "out");
+        assert_eq!(output.stderr, b"err\xff");
+        assert!(output.stderr_string().is_err());
+        assert_eq!(output.stderr_lossy(), "err\u{FFFD}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_process() {
+        let handle =
+            ProcessHandle::new(create_process(proc("sleep".into(), vec!["10".into()])).run());
+
+        signal_process(handle.clone(), Signal::Term).run();
+
+        assert_eq!(
+            wait_for_process(handle.clone()).run(),
+            ExitCode::Signaled(libc::SIGTERM, false)
+        );
+        // The process is gone, so there is nothing left to signal
+        signal_process(handle, Signal::Kill).run();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_process_group_after_leader_exits() {
+        use std::io::Read;
+        use std::time::{Duration, Instant};
+
+        // The leader exits at once, leaving a member of its group holding stdout open
+        let mut child = create_process(
+            shell("sleep 10 & exit 0")
+                .create_group(true)
+                .with_stdout(StdStream::CreatePipe),
+        )
+        .run();
+        let mut stdout = child.stdout.take().unwrap();
+        let handle = ProcessHandle::new(child);
+        let start = Instant::now();
+
+        assert_eq!(wait_for_process(handle.clone()).run(), ExitCode::Success);
+        signal_process_group_of(handle.clone(), Signal::Term).run();
+
+        // EOF once the background sleep is dead
+        stdout.read_to_end(&mut Vec::new()).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The whole group is gone now
+        signal_process_group_of(handle, Signal::Term).run();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_interrupt_process_group_of() {
+        let handle = ProcessHandle::new(
+            create_process(proc("sleep".into(), vec!["10".into()]).new_session(true)).run(),
+        );
+
+        interrupt_process_group_of(handle.clone()).run();
+
+        assert_eq!(
+            wait_for_process(handle).run(),
+            ExitCode::Signaled(libc::SIGINT, false)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_terminate_process_gracefully() {
+        use std::io::BufRead;
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::Duration;
+
+        let polite =
+            ProcessHandle::new(create_process(proc("sleep".into(), vec!["10".into()])).run());
+        let status = terminate_process_gracefully(polite, Duration::from_secs(5)).run();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+
+        let mut child = create_process(
+            shell("trap '' TERM; echo ready; while :; do sleep 0.01; done")
+                .with_stdout(StdStream::CreatePipe),
+        )
+        .run();
+        let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+        stdout.read_line(&mut String::new()).unwrap();
+
+        let stubborn = ProcessHandle::new(child);
+        let status = terminate_process_gracefully(stubborn, Duration::from_millis(100)).run();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn test_read_pipeline() {
+        let sorted = read_pipeline(
+            vec![
+                shell("printf 'b\\na\\n'"),
+                proc("sort".into(), vec![]),
+                shell("tr a-z A-Z"),
+            ],
+            String::new(),
+        )
+        .run();
+        let counted = read_pipeline(
+            vec![proc("cat".into(), vec![]), shell("wc -c | tr -d ' '")],
+            "hello".to_string(),
+        )
+        .run();
+
+        assert_eq!(sorted, "A\nB\n");
+        assert_eq!(counted, "5\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_pipeline_cleans_up_on_error() {
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let result = try_read_pipeline(
+            vec![
+                shell("sleep 10"),
+                proc("entoli-no-such-program".into(), vec![]),
+            ],
+            String::new(),
+        )
+        .run();
+
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_create_pipe() {
+        use std::io::Read;
+
+        let (mut reader, writer) = create_pipe().run();
+        let child =
+            create_process(shell("echo hi").with_stdout(StdStream::UseHandle(writer.into()))).run();
+
+        // The child holds the only write end, so this reads until it exits
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, "hi\n");
+        assert_eq!(wait_for_process(child).run(), ExitCode::Success);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_named_pipe() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let path = std::env::temp_dir().join(format!("entoli-fifo-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        create_named_pipe(path.clone(), 0o600).run();
+        let is_fifo = std::fs::metadata(&path).unwrap().file_type().is_fifo();
+        let again = try_create_named_pipe(path.clone(), 0o600).run();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(is_fifo);
+        assert!(again.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_handle_shared_between_threads() {
+        let handle =
+            ProcessHandle::new(create_process(proc("sleep".into(), vec!["10".into()])).run());
+        let waiter = {
+            let handle = handle.clone();
+            std::thread::spawn(move || wait_for_process(handle).run())
+        };
+
+        assert_eq!(get_pid(handle.clone()).run(), handle.id());
+        assert_eq!(get_process_exit_code(handle.clone()).run(), None);
+
+        // The waiting thread doesn't hold the handle, so it can still be killed from here
+        terminate_process(handle.clone()).run();
+
+        assert_eq!(
+            waiter.join().unwrap(),
+            ExitCode::Signaled(libc::SIGKILL, false)
+        );
+        assert_eq!(
+            get_process_exit_code(handle.clone()).run(),
+            Some(ExitCode::Signaled(libc::SIGKILL, false))
+        );
+        // Killing a reaped process does nothing
+        terminate_process(handle).run();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_code_conversions() {
+        let status = |code: ExitCode| std::process::ExitStatus::from(code);
+
+        for code in [
+            ExitCode::Success,
+            ExitCode::Failure(3),
+            ExitCode::Signaled(libc::SIGKILL, false),
+            ExitCode::Signaled(libc::SIGSEGV, true),
+        ] {
+            assert_eq!(ExitCode::from(status(code)), code);
+        }
+        assert_eq!(
+            wait_for_process(create_process(shell("kill -TERM $$")).run()).run(),
+            ExitCode::Signaled(libc::SIGTERM, false)
+        );
+
+        // Failures never turn into success when truncated to a byte
+        assert_eq!(ExitCode::Failure(256).status_byte(), 1);
+        assert_eq!(ExitCode::Failure(0).status_byte(), 1);
+        assert_eq!(ExitCode::Failure(-1).status_byte(), 255);
+        assert_eq!(ExitCode::Signaled(libc::SIGTERM, false).status_byte(), 143);
+        assert_eq!(
+            ExitCode::from(status(ExitCode::Failure(256))),
+            ExitCode::Failure(1)
+        );
+        assert_eq!(
+            std::process::ExitCode::from(ExitCode::Failure(256)),
+            std::process::ExitCode::FAILURE
+        );
+        assert_eq!(
+            std::process::ExitCode::from(ExitCode::Success),
+            std::process::ExitCode::SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_stream_process_lines() {
+        let mut lines = stream_process_lines(shell("printf 'a\\nb\\377\\n'; exit 2")).run();
+
+        assert_eq!(lines.by_ref().collect::<Vec<_>>(), vec!["a", "b\u{FFFD}"]);
+        assert_eq!(lines.exit_code(), Some(ExitCode::Failure(2)));
+
+        // A piped stdin is closed, so `cat` ends instead of blocking the iterator
+        let mut cat =
+            stream_process_lines(proc("cat".into(), vec![]).with_stdin(StdStream::CreatePipe))
+                .run();
+
+        assert_eq!(cat.next(), None);
+        assert_eq!(cat.exit_code(), Some(ExitCode::Success));
+    }
+
+    #[test]
+    fn test_stream_process_events() {
+        let events: Vec<_> = stream_process_tagged_lines(shell("echo out; echo err >&2"))
+            .run()
+            .collect();
+
+        assert_eq!(events.len(), 3);
+        assert!(events.contains(&ProcessEvent::Stdout("out".to_string())));
+        assert!(events.contains(&ProcessEvent::Stderr("err".to_string())));
+        assert_eq!(events[2], ProcessEvent::Exit(ExitCode::Success));
+
+        let stdout: Vec<u8> = stream_process_chunks(shell("head -c 100000 /dev/zero"))
+            .run()
+            .flat_map(|event| match event {
+                ProcessEvent::Stdout(chunk) => chunk,
+                _ => Vec::new(),
+            })
+            .collect();
+
+        assert_eq!(stdout.len(), 100000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dropped_stream_kills_process() {
+        let mut lines = stream_process_lines(shell("echo ready; exec sleep 10")).run();
+        let handle = lines.handle();
+
+        assert_eq!(lines.next().as_deref(), Some("ready"));
+        drop(lines);
+
+        assert_eq!(
+            get_process_exit_code(handle).run(),
+            Some(ExitCode::Signaled(libc::SIGKILL, false))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_process_timeout() {
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let child = create_process(
+            proc("sleep".into(), vec!["10".into()]).timeout(Duration::from_millis(100)),
+        )
+        .run();
+
+        assert_eq!(
+            wait_for_process(child).run(),
+            ExitCode::Signaled(libc::SIGKILL, false)
+        );
+
+        // The background sleep holds stdout open, so this only returns once the group is killed
+        let output = read_create_process_with_output(
+            shell("sleep 10 & wait")
+                .new_session(true)
+                .timeout(Duration::from_millis(100)),
+            vec![],
+        )
+        .run();
+
+        assert_eq!(output.exit_code, ExitCode::Signaled(libc::SIGKILL, false));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let fast = proc("true".into(), vec![]).timeout(Duration::from_secs(10));
+        assert_eq!(
+            wait_for_process(create_process(fast).run()).run(),
+            ExitCode::Success
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_files_limit() {
+        let read = |process: CreateProcess| {
+            read_create_process(process.with_stdout(StdStream::CreatePipe), String::new()).run()
+        };
+
+        assert_eq!(read(shell("ulimit -n").open_files_limit(64)), "64\n");
+
+        // Above the hard limit the limit is clamped rather than failing the spawn
+        let mut hard: libc::rlimit = unsafe { std::mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut hard) },
+            0
+        );
+        let clamped = read(shell("ulimit -n").open_files_limit(u64::MAX));
+        let expected = if hard.rlim_max == libc::RLIM_INFINITY {
+            "unlimited".to_string()
+        } else {
+            hard.rlim_max.to_string()
+        };
+        assert_eq!(clamped.trim(), expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_close_fds() {
+        // std opens everything close-on-exec, so make a descriptor a child would inherit
+        let fd = unsafe { libc::dup(2) };
+        assert!(fd > 2);
+
+        let check = format!("test -e /proc/self/fd/{} && echo open || echo closed", fd);
+        let inherited = read_create_process(
+            shell(check.clone()).with_stdout(StdStream::CreatePipe),
+            String::new(),
+        )
+        .run();
+        let closed = read_create_process(
+            shell(check)
+                .close_fds(true)
+                .with_stdout(StdStream::CreatePipe),
+            String::new(),
+        )
+        .run();
+        unsafe { libc::close(fd) };
+
+        assert_eq!(inherited, "open\n");
+        assert_eq!(closed, "closed\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_new_session() {
+        let handle = ProcessHandle::new(
+            create_process(proc("sleep".into(), vec!["10".into()]).new_session(true)).run(),
+        );
+        let pid = handle.id() as libc::pid_t;
+
+        let (sid, pgid) = unsafe { (libc::getsid(pid), libc::getpgid(pid)) };
+        terminate_process(handle).run();
+
+        assert_eq!(sid, pid);
+        assert_eq!(pgid, pid);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_for_process_with_usage() {
+        let busy = shell("i=0; while [ $i -lt 100000 ]; do i=$((i + 1)); done");
+        let handle = ProcessHandle::new(create_process(busy).run());
+
+        let (code, usage) = wait_for_process_with_usage(handle.clone()).run();
+
+        assert_eq!(code, ExitCode::Success);
+        assert!(usage.user_time + usage.system_time > std::time::Duration::ZERO);
+        assert!(usage.max_rss > 0);
+        // Kept once reaped, for every clone
+        assert_eq!(wait_for_process_with_usage(handle).run(), (code, usage));
+    }
+
+    #[test]
+    fn test_terminate_process_gracefully_keeps_handle_usable() {
+        use std::io::BufRead;
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::{Duration, Instant};
+
+        let mut child = create_process(
+            shell("trap '' TERM; echo ready; while :; do sleep 0.01; done")
+                .with_stdout(StdStream::CreatePipe),
+        )
+        .run();
+        let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+        stdout.read_line(&mut String::new()).unwrap();
+
+        let handle = ProcessHandle::new(child);
+        let terminating = handle.clone();
+        let terminator = std::thread::spawn(move || {
+            terminate_process_gracefully(terminating, Duration::from_millis(500)).run()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+
+        // Answered during the grace period rather than after it
+        let start = Instant::now();
+        assert!(get_process_exit_code(handle.clone()).run().is_none());
+        signal_process(handle, Signal::Cont).run();
+        assert!(start.elapsed() < Duration::from_millis(200));
+
+        assert_eq!(terminator.join().unwrap().signal(), Some(libc::SIGKILL));
+    }
+}
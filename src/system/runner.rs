@@ -0,0 +1,454 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use super::process::{
+    communicate, Child, CmdSpec, CreateProcess, ExitCode, ProcessOutput, StdStream,
+};
+use crate::{error::IoResult, prelude::Io};
+
+/// Starts the processes behind every `system::process` action
+///
+/// The runner is chosen per thread with `with_process_runner`, defaulting to `SystemProcessRunner`.
+pub trait ProcessRunner: Send + Sync {
+    /// Start the process, which may be a `Child::exited` standing in for it
+    fn spawn(&self, process: CreateProcess) -> IoResult<Child>;
+
+    /// Run to completion, feeding `stdin` if it is piped
+    fn output(&self, process: CreateProcess, stdin: Vec<u8>) -> IoResult<std::process::Output>;
+}
+
+/// Runs real processes through the operating system
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemProcessRunner;
+
+impl ProcessRunner for SystemProcessRunner {
+    fn spawn(&self, process: CreateProcess) -> IoResult<Child> {
+        Ok(process.spawn()?)
+    }
+
+    fn output(&self, process: CreateProcess, stdin: Vec<u8>) -> IoResult<std::process::Output> {
+        communicate(self.spawn(process)?, stdin)
+    }
+}
+
+thread_local! {
+    static CURRENT_RUNNER: RefCell<Option<Arc<dyn ProcessRunner>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn current_runner() -> Arc<dyn ProcessRunner> {
+    CURRENT_RUNNER.with(|current| {
+        current
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| Arc::new(SystemProcessRunner))
+    })
+}
+
+struct RestoreRunner(Option<Arc<dyn ProcessRunner>>);
+
+impl Drop for RestoreRunner {
+    fn drop(&mut self) {
+        CURRENT_RUNNER.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// The runner set on this thread, for `control::concurrent` to pass on to the threads it starts
+pub(crate) fn thread_runner() -> Option<Arc<dyn ProcessRunner>> {
+    CURRENT_RUNNER.with(|current| current.borrow().clone())
+}
+
+/// Run `f` with `runner` as this thread's runner, restoring the previous one afterwards
+pub(crate) fn run_with_runner<T>(
+    runner: Option<Arc<dyn ProcessRunner>>,
+    f: impl FnOnce() -> T,
+) -> T {
+    let previous = CURRENT_RUNNER.with(|current| current.replace(runner));
+    let _restore = RestoreRunner(previous);
+
+    f()
+}
+
+pub struct WithProcessRunnerIo<I> {
+    runner: Arc<dyn ProcessRunner>,
+    io: I,
+}
+
+impl<I> Io for WithProcessRunnerIo<I>
+where
+    I: Io,
+{
+    type Output = I::Output;
+
+    fn run(self) -> Self::Output {
+        let io = self.io;
+        run_with_runner(Some(self.runner), || io.run())
+    }
+}
+
+/// Run `io` with process actions on this thread going through `runner`
+///
+/// The previous runner is restored afterwards, even if `io` panics. Threads started inside `io`
+/// through `control::concurrent`, such as by `fork_io`, `timeout` or `map_concurrently`, use
+/// `runner` too; threads started any other way use the default runner.
+pub fn with_process_runner<R, I>(runner: R, io: I) -> WithProcessRunnerIo<I>
+where
+    R: ProcessRunner + 'static,
+    I: Io,
+{
+    WithProcessRunnerIo {
+        runner: Arc::new(runner),
+        io,
+    }
+}
+
+// Fake runner
+
+/// A process started through a `FakeProcessRunner`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invocation {
+    pub cmd_spec: CmdSpec,
+    pub cwd: Option<PathBuf>,
+    pub env: Option<Vec<(String, String)>>,
+    pub stdin: Vec<u8>,
+    pub spawned: bool,
+}
+
+#[derive(Default)]
+struct FakeState {
+    invocations: Vec<Invocation>,
+    responses: Vec<(CmdSpec, ProcessOutput)>,
+    default_response: Option<ProcessOutput>,
+}
+
+/// Records every process instead of running it, answering with scripted output
+///
+/// Clones share their script and records, so a test can keep one to inspect afterwards.
+/// Every process gets the response scripted for its `CmdSpec`, or the default response, which
+/// succeeds with no output unless set.
+///
+/// Nothing is started: processes which run to completion get the response directly, and spawned
+/// processes get a `Child::exited` with the response's exit code. Its piped stdout and stderr
+/// replay the response from in-process pipes, so streaming, pipelines and `with_create_process`
+/// work as usual. Inherited streams get the response too, while a piped stdin is read and thrown
+/// away. Output to a stream given as `StdStream::UseHandle` is dropped.
+#[derive(Clone, Default)]
+pub struct FakeProcessRunner {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeProcessRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(self, cmd_spec: CmdSpec, output: ProcessOutput) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .responses
+            .push((cmd_spec, output));
+        self
+    }
+
+    /// Script a command to print `stdout` and exit with `exit_code`
+    pub fn respond_with(self, cmd_spec: CmdSpec, exit_code: ExitCode, stdout: &str) -> Self {
+        self.respond(
+            cmd_spec,
+            ProcessOutput {
+                exit_code,
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: Vec::new(),
+            },
+        )
+    }
+
+    pub fn default_response(self, output: ProcessOutput) -> Self {
+        self.state.lock().unwrap().default_response = Some(output);
+        self
+    }
+
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.state.lock().unwrap().invocations.clone()
+    }
+
+    pub fn commands(&self) -> Vec<CmdSpec> {
+        self.invocations()
+            .into_iter()
+            .map(|invocation| invocation.cmd_spec)
+            .collect()
+    }
+
+    fn response(&self, cmd_spec: &CmdSpec) -> ProcessOutput {
+        let state = self.state.lock().unwrap();
+        state
+            .responses
+            .iter()
+            .find(|(scripted, _)| scripted == cmd_spec)
+            .map(|(_, output)| output.clone())
+            .or_else(|| state.default_response.clone())
+            .unwrap_or(ProcessOutput {
+                exit_code: ExitCode::Success,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            })
+    }
+
+    fn record(&self, process: &CreateProcess, stdin: Vec<u8>, spawned: bool) {
+        self.state.lock().unwrap().invocations.push(Invocation {
+            cmd_spec: process.cmd_spec().clone(),
+            cwd: process.get_cwd().cloned(),
+            env: process.get_env().map(<[_]>::to_vec),
+            stdin,
+            spawned,
+        });
+    }
+}
+
+/// Turn one end of an in-process pipe into the pipe type of a child
+#[cfg(unix)]
+fn child_pipe<T: From<std::os::fd::OwnedFd>>(pipe: impl Into<std::os::fd::OwnedFd>) -> T {
+    T::from(pipe.into())
+}
+
+#[cfg(windows)]
+fn child_pipe<T: From<std::os::windows::io::OwnedHandle>>(
+    pipe: impl Into<std::os::windows::io::OwnedHandle>,
+) -> T {
+    T::from(pipe.into())
+}
+
+/// Replay `output` on a scripted child's stdout or stderr, returning the pipe if it is piped
+///
+/// Inherited output goes through `print` so test harnesses capture it.
+fn replay_output(
+    stream: StdStream,
+    output: Vec<u8>,
+    print: impl FnOnce(&str),
+) -> IoResult<Option<std::io::PipeReader>> {
+    match stream {
+        StdStream::CreatePipe => {
+            let (reader, mut writer) = std::io::pipe()?;
+            // From a thread, as the output may not fit in the pipe; a closed reader ends it
+            std::thread::spawn(move || writer.write_all(&output));
+            Ok(Some(reader))
+        }
+        StdStream::Inherit => {
+            print(&String::from_utf8_lossy(&output));
+            Ok(None)
+        }
+        StdStream::UseHandle(_) | StdStream::NoStream => Ok(None),
+    }
+}
+
+/// Give a scripted child a stdin which is read and thrown away, like a process ignoring it
+fn discarded_input(stream: StdStream) -> IoResult<Option<std::process::ChildStdin>> {
+    match stream {
+        StdStream::CreatePipe => {
+            let (mut reader, writer) = std::io::pipe()?;
+            std::thread::spawn(move || std::io::copy(&mut reader, &mut std::io::sink()));
+            Ok(Some(child_pipe(writer)))
+        }
+        _ => Ok(None),
+    }
+}
+
+impl ProcessRunner for FakeProcessRunner {
+    fn spawn(&self, process: CreateProcess) -> IoResult<Child> {
+        self.record(&process, Vec::new(), true);
+
+        let response = self.response(process.cmd_spec());
+        let (stdin, stdout, stderr) = process.into_std_streams();
+
+        let mut child = Child::exited(response.exit_code.into());
+        child.stdin = discarded_input(stdin)?;
+        child.stdout =
+            replay_output(stdout, response.stdout, |text| print!("{text}"))?.map(child_pipe);
+        child.stderr =
+            replay_output(stderr, response.stderr, |text| eprint!("{text}"))?.map(child_pipe);
+        Ok(child)
+    }
+
+    fn output(&self, process: CreateProcess, stdin: Vec<u8>) -> IoResult<std::process::Output> {
+        self.record(&process, stdin, false);
+
+        let response = self.response(process.cmd_spec());
+
+        Ok(std::process::Output {
+            status: response.exit_code.into(),
+            stdout: response.stdout,
+            stderr: response.stderr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::control::concurrent::{
+        concurrently, fork_io, map_concurrently, timeout, wait_thread,
+    };
+    use crate::system::process::{
+        call_command, read_pipeline, read_process, read_process_with_exit_code, shell,
+        spawn_command, stream_process_lines, try_call_command, wait_for_process,
+        with_create_process, StdStream,
+    };
+
+    #[test]
+    fn test_fake_runner_records() {
+        let runner = FakeProcessRunner::new();
+
+        with_process_runner(runner.clone(), call_command("rm -rf build".to_string())).run();
+
+        assert_eq!(
+            runner.commands(),
+            vec![CmdSpec::Shell("rm -rf build".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_fake_runner_responds() {
+        let runner = FakeProcessRunner::new()
+            .respond_with(
+                CmdSpec::Raw("git".into(), vec!["rev-parse".into()]),
+                ExitCode::Success,
+                "abc\n",
+            )
+            .respond_with(CmdSpec::Shell("false".into()), ExitCode::Failure(1), "");
+
+        let io =
+            read_process("git".into(), vec!["rev-parse".into()], "in".into()).and_then(|out| {
+                read_process_with_exit_code("grep".into(), vec![], out).map(move |(_, code)| code)
+            });
+        let code = with_process_runner(runner.clone(), io).run();
+        let failed = with_process_runner(runner.clone(), try_call_command("false".into())).run();
+
+        assert_eq!(code, ExitCode::Success);
+        assert!(failed.is_err());
+        assert_eq!(runner.invocations()[0].stdin, b"in".to_vec());
+        assert_eq!(runner.invocations()[1].stdin, b"abc\n".to_vec());
+    }
+
+    #[test]
+    fn test_fake_runner_scripts_spawned_processes() {
+        let runner = FakeProcessRunner::new()
+            .respond(
+                CmdSpec::Shell("build".into()),
+                ProcessOutput {
+                    exit_code: ExitCode::Failure(3),
+                    stdout: b"one\ntwo %s\\n\n".to_vec(),
+                    stderr: b"warn\0\xff".to_vec(),
+                },
+            )
+            .respond_with(CmdSpec::Shell("sort".into()), ExitCode::Success, "a\nb\n");
+
+        let mut lines =
+            with_process_runner(runner.clone(), stream_process_lines(shell("build"))).run();
+        let stdout: Vec<_> = lines.by_ref().collect();
+        let output = with_process_runner(
+            runner.clone(),
+            with_create_process(
+                shell("build").with_stderr(StdStream::CreatePipe),
+                |_, _, stderr, handle| {
+                    let mut bytes = Vec::new();
+                    std::io::Read::read_to_end(&mut stderr.unwrap(), &mut bytes).unwrap();
+                    wait_for_process(handle).map(move |code| (code, bytes))
+                },
+            ),
+        )
+        .run();
+        let piped = with_process_runner(
+            runner.clone(),
+            read_pipeline(vec![shell("build"), shell("sort")], String::new()),
+        )
+        .run();
+
+        assert_eq!(stdout, vec!["one", "two %s\\n"]);
+        assert_eq!(lines.exit_code(), Some(ExitCode::Failure(3)));
+        assert_eq!(output, (ExitCode::Failure(3), b"warn\0\xff".to_vec()));
+        assert_eq!(piped, "a\nb\n");
+        assert!(runner
+            .invocations()
+            .iter()
+            .all(|invocation| invocation.spawned));
+        assert_eq!(runner.invocations().len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fake_runner_scripts_signals() {
+        let runner = FakeProcessRunner::new().respond_with(
+            CmdSpec::Shell("crash".into()),
+            ExitCode::Signaled(libc::SIGKILL, false),
+            "",
+        );
+        let code = with_process_runner(
+            runner,
+            spawn_command("crash".into()).and_then(wait_for_process),
+        )
+        .run();
+
+        assert_eq!(code, ExitCode::Signaled(libc::SIGKILL, false));
+    }
+
+    #[test]
+    fn test_fake_runner_reaches_concurrent_threads() {
+        let runner = FakeProcessRunner::new();
+        let io = fork_io(call_command("a".into()))
+            .and_then(|handle| wait_thread(handle).map(|result| result.unwrap()))
+            .then(timeout(Duration::from_secs(5), call_command("b".into())))
+            .then(concurrently(
+                call_command("c".into()),
+                call_command("d".into()),
+            ))
+            .then(map_concurrently(2, vec!["e", "f"], |name: &str| {
+                call_command(name.to_string())
+            }));
+
+        with_process_runner(runner.clone(), io).run();
+
+        let mut commands = runner.commands();
+        commands.sort_by_key(|cmd_spec| cmd_spec.to_shell_string());
+        assert_eq!(
+            commands,
+            ["a", "b", "c", "d", "e", "f"]
+                .map(|name| CmdSpec::Shell(name.to_string()))
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_fake_runner_spawns_in_process() {
+        let runner = FakeProcessRunner::new()
+            .respond_with(CmdSpec::Shell("wrap".into()), ExitCode::Failure(256), "out")
+            .respond_with(CmdSpec::Shell("cat".into()), ExitCode::Success, "");
+
+        let (pid, code) = with_process_runner(
+            runner.clone(),
+            spawn_command("wrap".into()).and_then(|child| {
+                let pid = child.id();
+                wait_for_process(child).map(move |code| (pid, code))
+            }),
+        )
+        .run();
+        let fed = with_process_runner(
+            runner,
+            with_create_process(
+                shell("cat").with_stdin(StdStream::CreatePipe),
+                |stdin, _, _, handle| {
+                    let written = stdin.unwrap().write_all(&vec![b'x'; 1 << 20]);
+                    wait_for_process(handle).map(move |code| (written.is_ok(), code))
+                },
+            ),
+        )
+        .run();
+
+        // Nothing real was started, and 256 mustn't wrap around to success
+        assert_eq!(pid, 0);
+        assert_ne!(code, ExitCode::Success);
+        assert_eq!(fed, (true, ExitCode::Success));
+    }
+}
@@ -0,0 +1,379 @@
+use std::borrow::Cow;
+use std::fmt;
+
+// Quoting
+
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_@%+:,./-".contains(c)
+}
+
+/// Quote a word so a POSIX shell reads it back unchanged
+///
+/// Words made only of safe characters are returned as they are. Words with `=` are quoted, as
+/// `sh` would take `FOO=bar` in command position for an assignment.
+pub fn quote(word: &str) -> Cow<'_, str> {
+    if !word.is_empty() && word.chars().all(is_safe) {
+        return Cow::Borrowed(word);
+    }
+
+    let mut quoted = String::with_capacity(word.len() + 2);
+    quoted.push('\'');
+    for c in word.chars() {
+        if c == '\'' {
+            quoted.push_str("'\\''");
+        } else {
+            quoted.push(c);
+        }
+    }
+    quoted.push('\'');
+
+    Cow::Owned(quoted)
+}
+
+/// Quote every word and join them with spaces, the inverse of `split`
+pub fn join<I, S>(words: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    words
+        .into_iter()
+        .map(|word| quote(word.as_ref()).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Parsing
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedSingleQuote,
+    UnterminatedDoubleQuote,
+    TrailingBackslash,
+    /// The line has no words, only blanks or a comment
+    Empty,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedSingleQuote => write!(f, "missing closing single quote"),
+            ParseError::UnterminatedDoubleQuote => write!(f, "missing closing double quote"),
+            ParseError::TrailingBackslash => write!(f, "backslash at end of input"),
+            ParseError::Empty => write!(f, "no command"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Split a command line into words the way a POSIX shell does, without expanding anything
+///
+/// Handles single and double quotes, backslash escapes, line continuations and `#` comments.
+pub fn split(line: &str) -> Result<Vec<String>, ParseError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '#' if !in_word => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    word.push(c);
+                    in_word = true;
+                }
+                None => return Err(ParseError::TrailingBackslash),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedSingleQuote),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(ParseError::UnterminatedDoubleQuote),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedDoubleQuote),
+                    }
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+// Templates
+
+/// Count the `{}` placeholders in `template`, and whether any sits inside shell quotes or after
+/// a backslash, where the quotes `render` adds would be taken literally
+const fn scan_template(template: &str) -> (usize, bool) {
+    let bytes = template.as_bytes();
+    let (mut count, mut quoted) = (0, false);
+    // The quote character while inside quotes, otherwise 0
+    let mut quote = 0;
+    let mut escaped = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let next = if i + 1 < bytes.len() { bytes[i + 1] } else { 0 };
+        match (bytes[i], next) {
+            (b'{', b'{') | (b'}', b'}') => {
+                escaped = false;
+                i += 2;
+            }
+            (b'{', b'}') => {
+                count += 1;
+                quoted |= quote != 0 || escaped;
+                escaped = false;
+                i += 2;
+            }
+            (c, _) => {
+                if escaped {
+                    escaped = false;
+                } else if quote == b'\'' {
+                    if c == b'\'' {
+                        quote = 0;
+                    }
+                } else if c == b'\\' {
+                    escaped = true;
+                } else if quote == b'"' {
+                    if c == b'"' {
+                        quote = 0;
+                    }
+                } else if c == b'\'' || c == b'"' {
+                    quote = c;
+                }
+                i += 1;
+            }
+        }
+    }
+
+    (count, quoted)
+}
+
+/// Number of `{}` placeholders in `template`, counted the same way as `render` does
+///
+/// Used by `cmd!` to check its arguments at compile time.
+pub const fn count_placeholders(template: &str) -> usize {
+    scan_template(template).0
+}
+
+/// Whether a `{}` in `template` is inside single or double quotes, or follows a backslash
+///
+/// `render` quotes every value itself, so such a placeholder would let the value escape its
+/// quotes. Used by `cmd!` to reject these templates at compile time.
+pub const fn has_quoted_placeholder(template: &str) -> bool {
+    scan_template(template).1
+}
+
+/// Substitute each `{}` in `template` with the next argument, quoted
+///
+/// `{{` and `}}` stand for literal braces. Used by `cmd!`, which checks the number of arguments
+/// at compile time.
+///
+/// # Panics
+///
+/// If the number of arguments differs from the number of placeholders, or if a placeholder is
+/// quoted, see `has_quoted_placeholder`.
+pub fn render(template: &str, args: &[&dyn fmt::Display]) -> String {
+    assert!(
+        !has_quoted_placeholder(template),
+        "cmd! placeholders are quoted for you and can't go inside quotes"
+    );
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => rendered.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => rendered.push('}'),
+            '{' if chars.next_if_eq(&'}').is_some() => {
+                let arg = args
+                    .next()
+                    .expect("cmd! has fewer arguments than placeholders");
+                rendered.push_str(&quote(&arg.to_string()));
+            }
+            c => rendered.push(c),
+        }
+    }
+
+    assert!(
+        args.next().is_none(),
+        "cmd! has more arguments than placeholders"
+    );
+
+    rendered
+}
+
+/// Build a shell `CreateProcess` from a template, quoting every interpolated value
+///
+/// A template whose placeholders don't match the arguments fails to compile, as does one with a
+/// placeholder inside quotes, which would let the value escape the quotes added for it.
+///
+/// ```ignore
+/// let process = cmd!("grep -r {} {} | wc -l", pattern, dir.display());
+/// ```
+///
+/// ```compile_fail
+/// let process = entoli::cmd!("grep -r {} {}", "pattern");
+/// ```
+///
+/// ```compile_fail
+/// let process = entoli::cmd!("echo '{}'", "a'; rm -rf /'");
+/// ```
+///
+/// ```compile_fail
+/// let process = entoli::cmd!("echo \"{}\"", "$(rm -rf /)");
+/// ```
+#[macro_export]
+macro_rules! cmd {
+    ($template:literal $(, $arg:expr)* $(,)?) => {{
+        const _: () = assert!(
+            $crate::system::shell::count_placeholders($template)
+                == [$(stringify!($arg)),*].len(),
+            "cmd! needs one argument per {{}} placeholder",
+        );
+        const _: () = assert!(
+            !$crate::system::shell::has_quoted_placeholder($template),
+            "cmd! placeholders are quoted for you and can't go inside quotes",
+        );
+
+        $crate::system::process::shell($crate::system::shell::render(
+            $template,
+            &[$(&$arg as &dyn ::std::fmt::Display),*],
+        ))
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::process::CmdSpec;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("abc-1.txt"), "abc-1.txt");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote("$HOME;rm"), "'$HOME;rm'");
+        assert_eq!(quote("FOO=bar"), "'FOO=bar'");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#"a 'b c' "d \"e\" \$f" g\ h # comment"#).unwrap(),
+            vec!["a", "b c", "d \"e\" $f", "g h"]
+        );
+        assert_eq!(split("a''b \"\"").unwrap(), vec!["ab", ""]);
+        assert_eq!(split("a \\\n b").unwrap(), vec!["a", "b"]);
+        assert_eq!(split("'a"), Err(ParseError::UnterminatedSingleQuote));
+        assert_eq!(split("\"a"), Err(ParseError::UnterminatedDoubleQuote));
+        assert_eq!(split("a\\"), Err(ParseError::TrailingBackslash));
+        assert_eq!(split(" # only a comment"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_join_split_round_trip() {
+        let words = vec![
+            "echo",
+            "",
+            "a b",
+            "it's",
+            "\"q\"",
+            "$x",
+            "back\\slash",
+            "#no",
+        ];
+
+        assert_eq!(split(&join(&words)).unwrap(), words);
+    }
+
+    #[test]
+    fn test_cmd_spec_render() {
+        let spec = CmdSpec::Raw("my prog".into(), vec!["--name".into(), "o'neil".into()]);
+
+        assert_eq!(spec.to_shell_string(), "'my prog' --name 'o'\\''neil'");
+        assert_eq!(CmdSpec::parse(&spec.to_shell_string()).unwrap(), spec);
+        assert_eq!(CmdSpec::parse("  # nothing"), Err(ParseError::Empty));
+        assert_eq!(
+            CmdSpec::Raw("FOO=bar".into(), vec![]).to_shell_string(),
+            "'FOO=bar'"
+        );
+    }
+
+    #[test]
+    fn test_cmd_macro() {
+        let name = "x; rm -rf /";
+        let process = cmd!("echo {} {{}}", name);
+
+        assert_eq!(
+            process.cmd_spec(),
+            &CmdSpec::Shell("echo 'x; rm -rf /' {}".to_string())
+        );
+        assert_eq!(count_placeholders("{} {{}} }}{{ {}{}"), 3);
+    }
+
+    #[test]
+    fn test_quoted_placeholders() {
+        assert!(!has_quoted_placeholder("echo {} '{{}}' \"a\" --x={}"));
+        assert!(!has_quoted_placeholder(r#"echo 'it\' {} "a\"b" {}"#));
+        assert!(has_quoted_placeholder("echo '{}'"));
+        assert!(has_quoted_placeholder("echo \"{}\""));
+        assert!(has_quoted_placeholder("echo \"$x {}\""));
+        assert!(has_quoted_placeholder(r"echo \{}"));
+        assert!(has_quoted_placeholder("echo 'a {}"));
+        assert_eq!(count_placeholders("echo '{}' \"{}\""), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't go inside quotes")]
+    fn test_render_rejects_single_quoted_placeholder() {
+        render("echo '{}'", &[&"a'; rm -rf /'"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't go inside quotes")]
+    fn test_render_rejects_double_quoted_placeholder() {
+        render("echo \"{}\"", &[&"$(rm -rf /)"]);
+    }
+}
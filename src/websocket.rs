@@ -1,15 +1,77 @@
 use std::net::TcpStream;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use tungstenite::stream::MaybeTlsStream;
 use tungstenite::WebSocket;
 use tungstenite::{connect, Message};
 use url::Url;
 
-use crate::prelude::Io;
+use crate::{
+    control::concurrent::{current_thread_killed, killed_error, CANCEL_POLL},
+    error::{Error, IoResult},
+    prelude::{Io, TryIo},
+};
 
 type Ws = Arc<Mutex<WebSocket<MaybeTlsStream<TcpStream>>>>;
 
+/// Set the timeout of blocking reads and writes, returning whether the stream supports it
+///
+/// Only plain TCP streams do; a TLS stream keeps blocking.
+fn set_poll_timeout(
+    socket: &WebSocket<MaybeTlsStream<TcpStream>>,
+    timeout: Option<Duration>,
+) -> std::io::Result<bool> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => {
+            stream.set_read_timeout(timeout)?;
+            stream.set_write_timeout(timeout)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn timed_out(error: &Error) -> bool {
+    match error {
+        Error::WebSocket(e) => matches!(
+            **e,
+            tungstenite::Error::Io(ref e)
+                if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+        ),
+        _ => false,
+    }
+}
+
+/// Run `f` on the socket until it stops timing out, checking between tries whether the thread
+/// was killed
+///
+/// The socket is unlocked between tries, so other threads can use it meanwhile.
+fn poll_socket<T>(
+    socket: &Ws,
+    mut f: impl FnMut(&mut WebSocket<MaybeTlsStream<TcpStream>>) -> IoResult<T>,
+) -> IoResult<T> {
+    loop {
+        let result = {
+            let mut socket = socket.lock().unwrap();
+            let polling = set_poll_timeout(&socket, Some(CANCEL_POLL))?;
+            let result = f(&mut socket);
+            set_poll_timeout(&socket, None)?;
+
+            match result {
+                Err(e) if polling && timed_out(&e) => None,
+                result => Some(result),
+            }
+        };
+
+        match result {
+            Some(result) => return result,
+            None if current_thread_killed() => return Err(killed_error().into()),
+            None => {}
+        }
+    }
+}
+
 pub struct WsRun<F> {
     pub url: Url,
     pub f: F,
@@ -34,6 +96,29 @@ pub fn ws_run<F>(url: Url, f: F) -> WsRun<F> {
     WsRun { url, f }
 }
 
+pub struct TryWsRun<F> {
+    pub url: Url,
+    pub f: F,
+}
+
+impl<I, F> Io for TryWsRun<F>
+where
+    I: TryIo,
+    I::Err: From<Error>,
+    F: FnMut(Ws) -> I,
+{
+    type Output = Result<I::Ok, I::Err>;
+
+    fn run(mut self) -> Self::Output {
+        let (socket, _) = connect(self.url.clone()).map_err(Error::from)?;
+        (self.f)(Arc::new(Mutex::new(socket))).try_run()
+    }
+}
+
+pub fn try_ws_run<F>(url: Url, f: F) -> TryWsRun<F> {
+    TryWsRun { url, f }
+}
+
 pub struct WsSend {
     pub socket: Ws,
     pub message: Message,
@@ -43,11 +128,7 @@ impl Io for WsSend {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        self.socket
-            .lock()
-            .unwrap()
-            .write_message(self.message)
-            .unwrap();
+        try_ws_send(self.socket, self.message).run().unwrap()
     }
 }
 
@@ -55,6 +136,32 @@ pub fn ws_send(socket: Ws, message: Message) -> WsSend {
     WsSend { socket, message }
 }
 
+pub struct TryWsSend {
+    pub socket: Ws,
+    pub message: Message,
+}
+
+impl Io for TryWsSend {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        let mut message = Some(self.message);
+
+        // A message cut short by a timeout stays queued in the socket until flushed
+        poll_socket(&self.socket, |socket| match message.take() {
+            Some(message) => Ok(socket.write_message(message)?),
+            None => Ok(socket.write_pending()?),
+        })
+    }
+}
+
+/// Send a message, failing with `ErrorKind::Interrupted` if the thread is killed meanwhile
+///
+/// A message interrupted part way may still be sent by a later send on the same socket.
+pub fn try_ws_send(socket: Ws, message: Message) -> TryWsSend {
+    TryWsSend { socket, message }
+}
+
 pub struct WsRecv {
     pub socket: Ws,
 }
@@ -63,7 +170,7 @@ impl Io for WsRecv {
     type Output = Message;
 
     fn run(self) -> Self::Output {
-        self.socket.lock().unwrap().read_message().unwrap()
+        try_ws_recv(self.socket).run().unwrap()
     }
 }
 
@@ -71,6 +178,23 @@ pub fn ws_recv(socket: Ws) -> WsRecv {
     WsRecv { socket }
 }
 
+pub struct TryWsRecv {
+    pub socket: Ws,
+}
+
+impl Io for TryWsRecv {
+    type Output = IoResult<Message>;
+
+    fn run(self) -> Self::Output {
+        poll_socket(&self.socket, |socket| Ok(socket.read_message()?))
+    }
+}
+
+/// Wait for a message, failing with `ErrorKind::Interrupted` if the thread is killed meanwhile
+pub fn try_ws_recv(socket: Ws) -> TryWsRecv {
+    TryWsRecv { socket }
+}
+
 pub struct WsClose {
     pub socket: Ws,
 }
@@ -79,10 +203,62 @@ impl Io for WsClose {
     type Output = ();
 
     fn run(self) -> Self::Output {
-        self.socket.lock().unwrap().close(None).unwrap();
+        try_ws_close(self.socket).run().unwrap()
     }
 }
 
 pub fn ws_close(socket: Ws) -> WsClose {
     WsClose { socket }
 }
+
+pub struct TryWsClose {
+    pub socket: Ws,
+}
+
+impl Io for TryWsClose {
+    type Output = IoResult<()>;
+
+    fn run(self) -> Self::Output {
+        Ok(self.socket.lock().unwrap().close(None)?)
+    }
+}
+
+pub fn try_ws_close(socket: Ws) -> TryWsClose {
+    TryWsClose { socket }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::control::concurrent::timeout;
+
+    #[test]
+    fn test_timeout_interrupts_ws_recv() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            tungstenite::accept(stream).unwrap().read_message().unwrap()
+        });
+
+        let (socket, _) = connect(Url::parse(&format!("ws://{}", addr)).unwrap()).unwrap();
+        let socket = Arc::new(Mutex::new(socket));
+
+        assert!(timeout(Duration::from_millis(50), ws_recv(socket.clone()))
+            .run()
+            .is_none());
+
+        // The interrupted receive gives the socket back instead of holding it forever
+        let start = Instant::now();
+        while Arc::strong_count(&socket) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        ws_send(socket, Message::Text("after".to_string())).run();
+        assert_eq!(server.join().unwrap(), Message::Text("after".to_string()));
+    }
+}